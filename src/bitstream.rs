@@ -0,0 +1,89 @@
+use crate::error::DecodeError;
+
+pub(crate) struct BitStream {
+    pub(crate) bit_index: u8,
+    pub(crate) byte_index: usize,
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) last_two_bits: u8,
+}
+
+impl BitStream {
+    pub(crate) fn new(bytes: &[u8]) -> BitStream {
+        BitStream {
+            bit_index: 0,
+            byte_index: 0,
+            last_two_bits: 0,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    pub(crate) fn next_bit(&mut self) {
+        self.bit_index += 1;
+        self.check_end_of_byte();
+        self.update_last_two_bits();
+    }
+
+    fn current_byte(&self) -> u8 {
+        match self.bytes.get(self.byte_index) {
+            Some(byte) => *byte,
+            None => 0,
+        }
+    }
+
+    // Reading past the end of the stream means the sprite was truncated
+    pub(crate) fn current_bit(&self) -> Result<u8, DecodeError> {
+        match self.bytes.get(self.byte_index) {
+            Some(byte) => Ok((byte >> (7 - self.bit_index)) & 0b00000001),
+            None => Err(self.truncated()),
+        }
+    }
+
+    fn update_last_two_bits(&mut self) {
+        let bit = (self.current_byte() >> (7 - self.bit_index)) & 0b00000001;
+        self.last_two_bits = ((self.last_two_bits << 1) | bit) & 0b00000011;
+    }
+
+    fn check_end_of_byte(&mut self) {
+        if self.bit_index > 7 {
+            self.bit_index = 0;
+            self.byte_index += 1;
+        }
+    }
+
+    pub(crate) fn read_bits(&mut self, bits_amount: u8, write_from_left: bool) -> Result<u8, DecodeError> {
+        let mut count: u8 = 0;
+        let mut byte: u8 = 0;
+
+        while count < bits_amount {
+            let bit = self.current_bit()?;
+            byte = match write_from_left {
+                true => byte | (bit << (7 - count)),
+                false => (byte << 1) | bit,
+            };
+
+            count += 1;
+            self.update_last_two_bits();
+            self.next_bit();
+        }
+
+        Ok(byte)
+    }
+
+    // Amount of bytes that have been read so far, counting the current one if we are in the middle of it
+    pub(crate) fn bytes_read(&self) -> usize {
+        if self.bit_index > 0 {self.byte_index + 1} else {self.byte_index}
+    }
+
+    // Position of the current bit counting from the first bit of the stream
+    pub(crate) fn bit_position(&self) -> usize {
+        self.byte_index * 8 + self.bit_index as usize
+    }
+
+    pub(crate) fn truncated(&self) -> DecodeError {
+        DecodeError::TruncatedStream { byte_offset: self.byte_index, bit_offset: self.bit_index }
+    }
+
+    pub(crate) fn overflow(&self) -> DecodeError {
+        DecodeError::BufferOverflow { byte_offset: self.byte_index, bit_offset: self.bit_index }
+    }
+}
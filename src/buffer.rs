@@ -0,0 +1,546 @@
+use std::cmp;
+
+use crate::bitstream::BitStream;
+use crate::error::DecodeError;
+use crate::sprite::{Bitplanes, SPRITE_PIXELS};
+
+const BUFFER_A_START: usize = 0;
+const BUFFER_A_END: usize = 391;
+const BUFFER_B_START: usize = 392;
+const BUFFER_B_END: usize = 783;
+const BUFFER_C_START: usize = 784;
+const BUFFER_C_END: usize = 1175;
+
+// The game keeps the three buffers at the start of the first SRAM bank (0xA000), which is 0x2000 bytes long
+const SRAM_SIZE: usize = 0x2000;
+
+// A dimension of 0 tiles makes the 8 bit counters of the game wrap around, so it works like 32 tiles
+pub(crate) fn tiles(dimension: u8) -> usize {
+    if dimension == 0 {32} else {dimension as usize}
+}
+
+pub(crate) struct Buffer {
+    pub(crate) bit_index: u8,
+    pub(crate) width: u8,
+    pub(crate) height: u8,
+    pub(crate) vertical_offset: u8,
+    pub(crate) horizontal_offset: u8,
+    pub(crate) byte_index: usize,
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) bitplane_length: usize,
+    pub(crate) row_index: usize,
+    // Emulate how glitched sprites overflow the buffers instead of stopping at the end of them
+    pub(crate) glitch: bool,
+    // Leave the sprite in the top left corner instead of centring it
+    pub(crate) no_centring: bool,
+}
+
+impl Buffer {
+    pub(crate) fn new() -> Buffer {
+        Buffer {
+            bit_index: 0,
+            byte_index: 0,
+            width: 0,
+            height: 0,
+            vertical_offset: 0,
+            horizontal_offset: 0,
+            bytes: Vec::new(),
+            bitplane_length: 0,
+            row_index: 0,
+            glitch: false,
+            no_centring: false,
+        }
+    }
+
+    // Buffer A => 0
+    // Buffer B => 1
+    // Buffer C => 2
+    fn get_buffer_address(buffer_number: u8) -> usize {
+        match buffer_number{
+            0 => BUFFER_A_START, // Address at 0
+            1 => BUFFER_B_START, // Address at 392
+            _ => BUFFER_C_START, // Address at 784
+        }
+    }
+
+    pub(crate) fn allocate_space(&mut self, width: u8, height: u8) {
+        const MAX_SPRITE_SIZE: u8 = 7; // 7 tiles
+        self.width = width;
+        self.height = height;
+        // We will need the vertical and horizontal offsets later, this is used to center the resulting
+        // sprite in a box of 7 * 7 tiles
+        // vertical offset = 7 - height, so the sprite sits on the bottom of the box
+        // horizontal offset = (7 - width) / 2, rounded up
+        // Glitched sprites can be bigger than 7 tiles, the subtractions wrap around just like in the game
+        if self.no_centring {
+            self.vertical_offset = 0;
+            self.horizontal_offset = 0;
+        } else {
+            self.vertical_offset = MAX_SPRITE_SIZE.wrapping_sub(height);
+            self.horizontal_offset = MAX_SPRITE_SIZE.wrapping_sub(width).wrapping_add(1) / 2;
+        }
+        // We need 3 bitplanes, the first and second ones are where the 
+        // decompressed bytes will be, which are 7 x 7 each.
+        // The third one is usually 7 x 7 maximum too, but glitched pokemon could
+        // have way more
+        // Note: Each tile has 64 pixels
+        // When emulating glitches, we have the whole SRAM bank for them to overflow into
+        self.bitplane_length = if self.glitch {
+            SRAM_SIZE
+        } else {
+            (7 * 7 * 2 * 8) + (cmp::max(7 * 7, width as usize * height as usize) * 8)
+        };
+        self.bytes = vec![0; self.bitplane_length];
+    }
+
+    // The stream is only needed to report where the decoding was if the pair does not fit
+    fn write_pair(&mut self, data: u8, stream: &BitStream) -> Result<(), DecodeError> {
+        let column_height = tiles(self.height) * 8;
+
+        match self.bytes.get_mut(self.byte_index) {
+            Some(byte) => *byte |= data << (8 - (self.bit_index + 2)),
+            // Past the SRAM bank the game writes into the work RAM, we can't show that
+            None if self.glitch => {},
+            None => return Err(stream.overflow()),
+        }
+
+        self.byte_index += 1;
+        self.row_index += 1;
+        // We have reached the end of the column
+        if self.row_index >= column_height {
+            self.row_index = 0;
+            self.byte_index -= column_height;
+            self.bit_index += 2;// Next column (in bits)
+            if self.bit_index >= 8 {
+                self.bit_index = 0;
+                // Jump to the next column
+                self.byte_index += column_height;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_zero_pairs(&mut self, zero_pairs_amount: usize, stream: &BitStream) -> Result<(), DecodeError> {
+        let mut count = 0;
+
+        while count < zero_pairs_amount {
+            self.write_pair(0, stream)?;
+            count += 1;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn decompress_to_bitplane(&mut self, bytes: &mut BitStream, initial_packet: u8, primary_buffer: bool) -> Result<(), DecodeError> {
+        let mut rle_length: u8 = 0;
+        let mut reading_first_rle = initial_packet == 0; // 1 for data packet and 0 for RLE packet
+        let mut reading_second_rle = false;
+        let mut first_rle_bits_read: u16 = 0;
+        let mut bits_written: usize = 0;
+        let mut data_pairs: usize = 0;
+        let bits_to_write: usize = tiles(self.width) * tiles(self.height) * 8 * 8;
+        // If the primary buffer is true, start decoding into buffer B at location 392,
+        // else, decode into buffer C at location 784
+        // Glitched pokemons overflow from Buffer B to C
+        self.byte_index = if primary_buffer {BUFFER_B_START} else {BUFFER_C_START};
+        self.bit_index = 0;
+        self.row_index = 0;
+
+        log::debug!("decompress buffer={} bits_to_write={}", if primary_buffer {"B"} else {"C"}, bits_to_write);
+        while bits_written < bits_to_write {
+
+            if reading_first_rle {
+                let current_bit = bytes.current_bit()?;
+                rle_length += 1; // We have to count the length of the rle packet even if the bit is zero
+                // A run this long could never fit in any buffer
+                if rle_length > 16 {
+                    return Err(bytes.overflow());
+                }
+                if current_bit == 0 {
+                    // Once we find a zero, we can start reading the amount of bits we counted
+                    // If the bit is 1, we increment the count by 1 and jump to the next bit
+                    first_rle_bits_read = (first_rle_bits_read << 1) | (current_bit as u16);
+                    reading_first_rle = false;
+                    reading_second_rle = true;
+                } else {
+                    // If the bit is 1, we increment the count by 1 and jump to the next bit
+                    first_rle_bits_read = (first_rle_bits_read << 1) | (current_bit as u16);
+                }
+                bytes.next_bit();
+                continue;
+            }
+            if reading_second_rle {
+                // Read the amount of bits we counted
+                let mut second_rle_bits_read = 0;
+                let mut rle_bits_count = 0;
+                while rle_bits_count < rle_length {
+                    let current_bit = bytes.current_bit()?;
+                    second_rle_bits_read = (second_rle_bits_read << 1) | (current_bit as u16);
+                    rle_bits_count += 1;
+                    bytes.next_bit();
+                }
+                // Then, we add the first 2 groups plus one (the plus one is to take care of
+                // the "offset" of the compression algorithm)
+                // This is the amount to zero pairs that we have to add to the buffer.
+                // For example, if the result is 4, we will have to add 4 zero pairs,
+                // or 8 zeros in total
+                // Just like the game, a run that goes past the end of the bitplane is cut short
+                let zero_pairs = first_rle_bits_read as usize + second_rle_bits_read as usize + 1;
+                let zero_pairs = cmp::min(zero_pairs, (bits_to_write - bits_written) / 2);
+                log::debug!("packet type=rle zero_pairs={}", zero_pairs);
+                self.write_zero_pairs(zero_pairs, bytes)?; // write the zero pairs to the buffer
+                bits_written += zero_pairs * 2;
+                first_rle_bits_read = 0;
+                rle_length = 0;
+                // Aftrer reading RLE packets, the next is a data packet until we find a 00 pair
+                reading_first_rle = false;
+                reading_second_rle = false;
+                continue;
+            }
+            // When we are not reading RLE packets, we can read the pairs of data (data packets) until
+            // we find a 00 pair
+            if !reading_first_rle && !reading_second_rle {
+                let bits_pair = bytes.read_bits(2, false)?;
+                if bits_pair == 0 {
+                    log::debug!("packet type=data pairs={}", data_pairs);
+                    data_pairs = 0;
+                    rle_length = 0;
+                    reading_first_rle = true;
+                    reading_second_rle = false;
+                } else {
+                    self.write_pair(bits_pair, bytes)?; // write the pair to the buffer
+                    bits_written += 2;
+                    data_pairs += 1;
+                }
+            }
+        }
+        // The last data packet of the bitplane doesn't need the 00 pair
+        if data_pairs > 0 {
+            log::debug!("packet type=data pairs={}", data_pairs);
+        }
+        log::debug!("decompress bytes_written={} stream_byte={} stream_bit={}", bits_written / 8, bytes.byte_index, bytes.bit_index);
+
+        Ok(())
+    }
+
+    pub(crate) fn delta_decode(&mut self, buffer_number: u8) {
+        let index_offset = Buffer::get_buffer_address(buffer_number);
+        let mut row_index: usize = 0;
+        let row_height = tiles(self.height) * 8; // Height in bits
+        let col_width = tiles(self.width); // Width in bytes
+        // The initial state is always zero at the beginning of each row
+        let delta_decode_nibble: [u8; 16] = [
+            0b0000, 0b0001, 0b0011, 0b0010,
+            0b0111, 0b0110, 0b0100, 0b0101,
+            0b1111, 0b1110, 0b1100, 0b1101,
+            0b1000, 0b1001, 0b1011, 0b1010,
+        ];
+
+        // We have to process row by row, then pairs of 4 bits for each column
+        while row_index < row_height {
+            let mut prev_state = 0;
+            let mut col_index: usize = 0;
+
+            while col_index < col_width {
+                // Calculate the index in the bytes
+                let index: usize = (col_index * row_height + row_index) + index_offset;
+                // Only glitched sprites can go past the end of the SRAM
+                if index >= self.bytes.len() {
+                    break;
+                }
+                let byte = self.bytes[index];
+
+                // Getting the first sub-column (4 bits)
+                let first = delta_decode_nibble[(byte >> 4) as usize] ^ (0b1111 * prev_state);
+                prev_state = first & 1;
+
+                // Then the second sub-column (4 bits)
+                let second = delta_decode_nibble[(byte & 0b1111) as usize] ^ (0b1111 * prev_state);
+                prev_state = second & 1;
+
+                // Combine the two
+                self.bytes[index] = (first << 4) + second;
+                col_index += 1;
+            }
+
+            row_index += 1;
+            self.byte_index += 1;
+        }
+    }
+
+    pub(crate) fn xor_buffers(&mut self, buffer_index: u8, replace_buffer: u8) {
+        let buffer_index_offset = Buffer::get_buffer_address(buffer_index);
+        let replace_index_offset = Buffer::get_buffer_address(replace_buffer);
+        // The game xors as many bytes as the sprite has, which can be way more than a buffer for glitched sprites
+        let end_index = if self.glitch {tiles(self.width) * tiles(self.height) * 8} else {8 * 7 * 7};
+        let mut index = 0;
+        while index < end_index {
+            if index + cmp::max(replace_index_offset, buffer_index_offset) >= self.bytes.len() {
+                break;
+            }
+
+            self.bytes[index + replace_index_offset] ^= self.bytes[index + buffer_index_offset];
+
+            index += 1;
+        }
+    }
+
+    fn wipe_bitplane(&mut self, buffer_number: u8) {
+        let offset = Buffer::get_buffer_address(buffer_number);
+
+        // Wipe the "to" bitplane first
+        let mut index = offset;
+        let buffer_size = 7 * 7 * 8;
+        while index < offset + buffer_size {
+            self.bytes[index] = 0;
+            index += 1;
+        }
+    }
+
+    pub(crate) fn copy_bitplane(&mut self, from: u8, to: u8) {
+
+        if self.glitch {
+            self.copy_bitplane_overflowing(from, to);
+            return;
+        }
+
+        self.wipe_bitplane(to);
+
+        let to_bitplane_start = Buffer::get_buffer_address(to);
+        let from_bitplane_start = Buffer::get_buffer_address(from);
+        let mut from_bitplane_index = from_bitplane_start;
+
+        // Step 1: calculate the offset of the top-left corner
+        let mut index: usize = ((self.vertical_offset as usize * 8) + (self.horizontal_offset as usize * 8 * 7)) + to_bitplane_start;
+
+        // Step 2: copy the columns (height) of tiles
+        let height = self.height as usize * 8;
+        let mut current_column = 0;
+        while current_column < self.width {
+            let mut row_count: usize = 0;
+            while row_count < height {
+                self.bytes[index] = self.bytes[from_bitplane_index];
+                index += 1;
+                from_bitplane_index += 1;
+                row_count += 1;
+            }
+
+            // Revert the pointer back to the previous offset and add 56
+            // This will put the pointer to the next offset vertical offset
+            index -= height;
+            index += 56;
+
+            current_column += 1;
+        }
+    }
+
+    // This is the copy exactly as the game does it, with 8 bit arithmetic everywhere:
+    // - The offset of the top-left corner is a single byte, so it wraps around
+    // - The columns are read the same way the decompression wrote them, `width` columns
+    //   of `height * 8` bytes
+    // - A size of 0 wraps around just like in the decompression, see `tiles`
+    // For every sprite that fits in the box this is the same as the normal copy,
+    // glitched ones get scattered over the buffers after the destination
+    fn copy_bitplane_overflowing(&mut self, from: u8, to: u8) {
+        self.wipe_bitplane(to);
+
+        let offset = self.horizontal_offset.wrapping_mul(7 * 8).wrapping_add(self.vertical_offset.wrapping_mul(8));
+        let mut column_start = Buffer::get_buffer_address(to) + offset as usize;
+        let mut from_bitplane_index = Buffer::get_buffer_address(from);
+        let columns = tiles(self.width);
+        let column_height = tiles(self.height) * 8;
+
+        let mut current_column = 0;
+        while current_column < columns {
+            let mut row_count: usize = 0;
+            while row_count < column_height {
+                let byte = self.bytes.get(from_bitplane_index).copied().unwrap_or(0);
+                if let Some(destination) = self.bytes.get_mut(column_start + row_count) {
+                    *destination = byte;
+                }
+                from_bitplane_index += 1;
+                row_count += 1;
+            }
+
+            column_start += 56;
+            current_column += 1;
+        }
+    }
+
+    // Back sprites are stored at 4x4 tiles and the game doubles them to fill the 7x7 tiles box
+    // instead of centring them. Like the game, the bitplane is always read as 4 columns of
+    // 32 rows and the last 4 rows and columns of pixels don't fit, so they are dropped
+    pub(crate) fn scale_bitplane(&mut self, from: u8, to: u8) {
+        const BACK_SPRITE_SIZE: usize = 4 * 8;
+
+        let to_bitplane_start = Buffer::get_buffer_address(to);
+        let from_bitplane_start = Buffer::get_buffer_address(from);
+
+        for column in 0..7 {
+            // Every column of 8 pixels comes from half a column of the original sprite,
+            // the left half for even columns and the right half for odd ones
+            let shift = if column % 2 == 0 {4} else {0};
+            for row in 0..SPRITE_PIXELS {
+                let byte = self.bytes[from_bitplane_start + (column / 2) * BACK_SPRITE_SIZE + row / 2];
+                let nibble = (byte >> shift) & 0b00001111;
+                let mut doubled = 0;
+                for bit in 0..4 {
+                    if (nibble >> bit) & 1 == 1 {
+                        doubled |= 0b11 << (bit * 2);
+                    }
+                }
+                self.bytes[to_bitplane_start + column * SPRITE_PIXELS + row] = doubled;
+            }
+        }
+    }
+
+    // Mirrors a centred bitplane like the game does for the sprites facing right: the 7 columns
+    // of 56 bytes go in the opposite order and the 8 pixels of every byte are reversed
+    pub(crate) fn flip_bitplane(&mut self, buffer_number: u8) {
+        let start = Buffer::get_buffer_address(buffer_number);
+        let bitplane = &mut self.bytes[start..start + 7 * SPRITE_PIXELS];
+        for column in 0..7 / 2 {
+            let (left, right) = bitplane.split_at_mut((6 - column) * SPRITE_PIXELS);
+            left[column * SPRITE_PIXELS..(column + 1) * SPRITE_PIXELS].swap_with_slice(&mut right[..SPRITE_PIXELS]);
+        }
+        for byte in bitplane.iter_mut() {
+            *byte = byte.reverse_bits();
+        }
+    }
+
+    pub(crate) fn zip_buffers(&mut self) {
+        let mut last_index_buffer_a: usize = BUFFER_A_END;
+        let mut last_index_buffer_b: usize = BUFFER_B_END;
+        let mut last_index_buffer_c: usize = BUFFER_C_END;
+
+        log::trace!(
+            "zip last_index_a={} last_index_b={} last_index_c={}",
+            last_index_buffer_a, last_index_buffer_b, last_index_buffer_c
+        );
+
+        loop {
+            self.bytes[last_index_buffer_c] = self.bytes[last_index_buffer_b];
+            last_index_buffer_c -= 1;
+            self.bytes[last_index_buffer_c] = self.bytes[last_index_buffer_a];
+
+            if last_index_buffer_a == 0 {
+                break;
+            }
+
+            last_index_buffer_a -= 1;
+            last_index_buffer_b -= 1;
+            last_index_buffer_c -= 1;
+        }
+    }
+
+    // Copies the decoded bitplanes out of buffers B and C, this only makes sense
+    // after the delta decoding and before the bitplanes are copied to their final place
+    pub(crate) fn bitplanes(&self) -> Bitplanes {
+        let plane_length = Bitplanes::plane_length(self.width, self.height);
+        Bitplanes {
+            width: self.width,
+            height: self.height,
+            low: self.bytes[BUFFER_B_START..BUFFER_B_START + plane_length].to_vec(),
+            high: self.bytes[BUFFER_C_START..BUFFER_C_START + plane_length].to_vec(),
+        }
+    }
+
+    // Each pair of bytes in buffers B and C is one row of 8 pixels, the first byte holds
+    // the low bit of every pixel and the second one the high bit. The tiles are laid out
+    // column by column, so after 56 rows we jump to the next column of tiles
+    pub(crate) fn pixels(&self) -> [[u8; SPRITE_PIXELS]; SPRITE_PIXELS] {
+        let mut pixels = [[0; SPRITE_PIXELS]; SPRITE_PIXELS];
+        let mut index = BUFFER_B_START;
+        let mut pixel_row = 0;
+        let mut pixel_col = 0;
+
+        while index <= BUFFER_C_END {
+            let mut bit_index = 0;
+
+            while bit_index <= 7 {
+                let low_bit = (self.bytes[index] >> (7 - bit_index)) & 0b00000001;
+                let high_bit = (self.bytes[index + 1] >> (7 - bit_index)) & 0b00000001;
+                pixels[pixel_row][pixel_col + bit_index] = (high_bit << 1) | low_bit;
+                bit_index += 1;
+            }
+
+            index += 2;
+            pixel_row += 1;
+            if pixel_row >= SPRITE_PIXELS {
+                pixel_row = 0;
+                pixel_col += 8;
+            }
+        }
+
+        pixels
+    }
+
+    // Logs the three buffers as rows of '@' (1) and '.' (0), one column of 8 pixels per byte.
+    // Every 56 bytes we jump to the next column, just like the tiles are laid out
+    pub(crate) fn trace_bitplanes(&self, stage: &str) {
+        if !log::log_enabled!(log::Level::Trace) {
+            return;
+        }
+
+        let pixel_height = 7 * 8;
+        let mut pixel_row = 0;
+        while pixel_row < pixel_height {
+            let row: String = self.bytes[..=BUFFER_C_END].iter().skip(pixel_row).step_by(pixel_height)
+                .map(|byte| format!("{:08b}", byte).replace('0', ".").replace('1', "@"))
+                .collect();
+            log::trace!("bitplanes stage={} row={} pixels={}", stage, pixel_row, row);
+            pixel_row += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every byte of the SRAM gets a value from its address before the copy, and afterwards each one
+    // must be where the comment of `copy_bitplane_overflowing` says: `width` columns of `height * 8`
+    // bytes from buffer B, each one 56 bytes after the previous one, starting at the wrapped offset.
+    // The columns are longer than 56 bytes, so every byte keeps what the last column wrote into it
+    fn assert_overflowing_copy(header: u8, offset: usize) {
+        let mut buffer = Buffer::new();
+        buffer.glitch = true;
+        buffer.allocate_space(header >> 4, header & 0x0F);
+        for (address, byte) in buffer.bytes.iter_mut().enumerate() {
+            *byte = (address % 251) as u8;
+        }
+        let before = buffer.bytes.clone();
+        buffer.copy_bitplane(1, 0);
+
+        let columns = tiles(buffer.width);
+        let column_height = tiles(buffer.height) * 8;
+        let end = offset + (columns - 1) * 56 + column_height;
+        for (address, byte) in buffer.bytes.iter().enumerate() {
+            let expected = if address >= offset && address < end {
+                let column = ((address - offset) / 56).min(columns - 1);
+                let row = address - offset - column * 56;
+                // Past the end of the SRAM the game reads the work RAM, which is taken as zeros
+                before.get(BUFFER_B_START + column * column_height + row).copied().unwrap_or(0)
+            } else if address <= BUFFER_A_END {
+                // Buffer A is wiped before the copy
+                0
+            } else {
+                before[address]
+            };
+            assert_eq!(*byte, expected, "header {:#04X}, address {}", header, address);
+        }
+    }
+
+    #[test]
+    fn sprites_of_8x8_tiles_overflow_into_buffer_b() {
+        // 7 - 8 wraps to 255 tiles down, 255 * 8 wraps to 248 bytes into buffer A
+        assert_overflowing_copy(0x88, 248);
+    }
+
+    #[test]
+    fn sprites_of_0x0_tiles_overflow_into_the_whole_sram() {
+        // 0 tiles is 32, the offsets are 4 tiles across and 7 down, 4 * 56 + 7 * 8 wraps to 24 bytes
+        assert_overflowing_copy(0x00, 24);
+    }
+}
@@ -0,0 +1,156 @@
+use std::fmt;
+
+use crate::bitstream::BitStream;
+use crate::buffer::tiles;
+use crate::error::DecodeError;
+use crate::EncodingMode;
+
+/// One field of a compressed sprite
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Field {
+    Width(u8),
+    Height(u8),
+    PrimaryBuffer(u8),
+    /// 0 if the bitplane starts with an RLE packet and 1 if it starts with a data packet
+    InitialPacket(u8),
+    /// A run of `length` bits (ones ended by a zero) followed by `length` more bits with the value
+    RlePacket { length: u8, value: u16, zero_pairs: usize },
+    /// Pairs of bits written as they are, ended by a 00 pair unless the bitplane was already full
+    DataPacket { pairs: Vec<u8>, terminated: bool },
+    EncodingMode(EncodingMode),
+}
+
+/// A field together with where it is in the stream and the bits it was read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    /// Offset of the first bit of the field, counting from the first bit of the stream
+    pub bit_offset: usize,
+    /// The bits of the field, groups are separated by spaces
+    pub bits: String,
+    pub field: Field,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, meaning) = match &self.field {
+            Field::Width(width) => ("width", format!("{} tiles", width)),
+            Field::Height(height) => ("height", format!("{} tiles", height)),
+            Field::PrimaryBuffer(buffer) => ("primary_buffer", format!("buffer {}", if *buffer == 0 {"B"} else {"C"})),
+            Field::InitialPacket(packet) => ("initial_packet", (if *packet == 0 {"RLE"} else {"data"}).to_string()),
+            Field::RlePacket { zero_pairs, .. } => ("rle", format!("{} zero pairs", zero_pairs)),
+            Field::DataPacket { pairs, terminated } => (
+                "data", format!("{} pairs{}", pairs.len(), if *terminated {""} else {", bitplane full"})
+            ),
+            Field::EncodingMode(mode) => ("encoding_mode", format!("{:?}", mode)),
+        };
+        write!(f, "{:>6} {:>4X}.{}  {:<15} {}  ; {}", self.bit_offset, self.bit_offset / 8, self.bit_offset % 8, name, self.bits, meaning)
+    }
+}
+
+// Reads the bits one by one, keeping a copy of them as text
+fn read_field(stream: &mut BitStream, bits_amount: u8, bits: &mut String) -> Result<u16, DecodeError> {
+    let mut value: u16 = 0;
+    let mut count = 0;
+    while count < bits_amount {
+        let bit = stream.current_bit()?;
+        bits.push(if bit == 0 {'0'} else {'1'});
+        value = (value << 1) | bit as u16;
+        stream.next_bit();
+        count += 1;
+    }
+    Ok(value)
+}
+
+// Reads a single field and records it
+fn push_field<F: Fn(u16) -> Field>(stream: &mut BitStream, bits_amount: u8, instructions: &mut Vec<Instruction>, field: F) -> Result<u16, DecodeError> {
+    let bit_offset = stream.bit_position();
+    let mut bits = String::new();
+    let value = read_field(stream, bits_amount, &mut bits)?;
+    instructions.push(Instruction { bit_offset, bits, field: field(value) });
+    Ok(value)
+}
+
+/// Walks a compressed sprite and lists every field it is made of, without decompressing it.
+///
+/// This follows the stream exactly like the decoder, so widths and heights bigger than
+/// 7 tiles are listed too (a dimension of 0 counts as 32 tiles, just like in the game).
+pub fn disassemble(bytes: &[u8]) -> Result<Vec<Instruction>, DecodeError> {
+    let mut stream = BitStream::new(bytes);
+    let mut instructions = Vec::new();
+
+    let width = push_field(&mut stream, 4, &mut instructions, |value| Field::Width(value as u8))? as u8;
+    let height = push_field(&mut stream, 4, &mut instructions, |value| Field::Height(value as u8))? as u8;
+    push_field(&mut stream, 1, &mut instructions, |value| Field::PrimaryBuffer(value as u8))?;
+    let pairs_amount = tiles(width) * tiles(height) * 8 * 8 / 2;
+
+    disassemble_bitplane(&mut stream, pairs_amount, &mut instructions)?;
+
+    // 0 is mode 1, 10 is mode 2 and 11 is mode 3
+    let bit_offset = stream.bit_position();
+    let mut bits = String::new();
+    let encoding_mode = if read_field(&mut stream, 1, &mut bits)? == 0 {
+        EncodingMode::Mode1
+    } else if read_field(&mut stream, 1, &mut bits)? == 0 {
+        EncodingMode::Mode2
+    } else {
+        EncodingMode::Mode3
+    };
+    instructions.push(Instruction { bit_offset, bits, field: Field::EncodingMode(encoding_mode) });
+
+    disassemble_bitplane(&mut stream, pairs_amount, &mut instructions)?;
+
+    Ok(instructions)
+}
+
+fn disassemble_bitplane(stream: &mut BitStream, pairs_amount: usize, instructions: &mut Vec<Instruction>) -> Result<(), DecodeError> {
+    let initial_packet = push_field(stream, 1, instructions, |value| Field::InitialPacket(value as u8))?;
+    let mut reading_rle = initial_packet == 0;
+    let mut pairs_written = 0;
+
+    while pairs_written < pairs_amount {
+        let bit_offset = stream.bit_position();
+        let mut bits = String::new();
+
+        if reading_rle {
+            // Count the bits until the first zero, then read that many bits more
+            let mut length: u8 = 0;
+            let mut prefix: u16 = 0;
+            loop {
+                let bit = read_field(stream, 1, &mut bits)?;
+                length += 1;
+                prefix = (prefix << 1) | bit;
+                if bit == 0 {
+                    break;
+                }
+                // A run this long could never fit in any buffer
+                if length >= 16 {
+                    return Err(stream.overflow());
+                }
+            }
+            bits.push(' ');
+            let value = read_field(stream, length, &mut bits)?;
+            let zero_pairs = prefix as usize + value as usize + 1;
+            pairs_written += zero_pairs;
+            instructions.push(Instruction { bit_offset, bits, field: Field::RlePacket { length, value, zero_pairs } });
+        } else {
+            let mut pairs = Vec::new();
+            let mut terminated = false;
+            while pairs_written < pairs_amount {
+                if !bits.is_empty() {
+                    bits.push(' ');
+                }
+                let pair = read_field(stream, 2, &mut bits)? as u8;
+                if pair == 0 {
+                    terminated = true;
+                    break;
+                }
+                pairs.push(pair);
+                pairs_written += 1;
+            }
+            instructions.push(Instruction { bit_offset, bits, field: Field::DataPacket { pairs, terminated } });
+        }
+        reading_rle = !reading_rle;
+    }
+
+    Ok(())
+}
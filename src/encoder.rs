@@ -0,0 +1,238 @@
+use crate::error::EncodeError;
+use crate::sprite::{Bitplanes, SPRITE_TILES};
+use crate::{decompress_bitplanes, EncodingMode};
+
+/// Size of the output of one encoding mode and primary buffer combination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    pub mode: EncodingMode,
+    pub primary_buffer: u8,
+    /// Length of the compressed sprite in bytes
+    pub size: usize,
+}
+
+/// The result of trying every encoding mode with both primary buffers
+#[derive(Debug, Clone)]
+pub struct OptimalEncoding {
+    /// The smallest compressed sprite
+    pub bytes: Vec<u8>,
+    pub mode: EncodingMode,
+    pub primary_buffer: u8,
+    /// Every combination that was tried, in the order they were tried
+    pub candidates: Vec<Candidate>,
+}
+
+// The opposite of the BitStream, bits are appended from the most significant one
+struct BitWriter {
+    bit_index: u8,
+    bytes: Vec<u8>,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter {
+            bit_index: 0,
+            bytes: Vec::new(),
+        }
+    }
+
+    fn write_bit(&mut self, bit: u8) {
+        if self.bit_index == 0 {
+            self.bytes.push(0);
+        }
+        let last_byte_index = self.bytes.len() - 1;
+        self.bytes[last_byte_index] |= (bit & 0b00000001) << (7 - self.bit_index);
+        self.bit_index = (self.bit_index + 1) % 8;
+    }
+
+    // Writes the lowest `bits_amount` bits of the value, most significant first
+    fn write_bits(&mut self, value: usize, bits_amount: u8) {
+        let mut count = bits_amount;
+        while count > 0 {
+            count -= 1;
+            self.write_bit(((value >> count) & 1) as u8);
+        }
+    }
+}
+
+/// Compresses a sprite with the given encoding mode and primary buffer.
+///
+/// The primary buffer is the bit stored in the header: 0 compresses the low bitplane (buffer B)
+/// first and 1 compresses the high bitplane (buffer C) first, any other value is an error.
+pub fn compress(planes: &Bitplanes, mode: EncodingMode, primary_buffer: u8) -> Result<Vec<u8>, EncodeError> {
+    let width = planes.width;
+    let height = planes.height;
+    if width == 0 || height == 0 || width as usize > SPRITE_TILES || height as usize > SPRITE_TILES {
+        return Err(EncodeError::InvalidDimensions { width, height });
+    }
+    if primary_buffer > 1 {
+        return Err(EncodeError::InvalidPrimaryBuffer { primary_buffer });
+    }
+    let plane_length = Bitplanes::plane_length(width, height);
+    for plane in &[&planes.low, &planes.high] {
+        if plane.len() != plane_length {
+            return Err(EncodeError::PlaneSizeMismatch { expected: plane_length, found: plane.len() });
+        }
+    }
+
+    // Undo what the decoder will do after decompressing:
+    // In any mode, buffer C gets delta-decoded
+    // In mode 1 and 3, buffer B gets delta-decoded too
+    // In mode 2 and 3, buffer B gets xored against buffer C at the very end
+    let buffer_c = delta_encode(&planes.high, width, height);
+    let buffer_b = match mode {
+        EncodingMode::Mode1 => delta_encode(&planes.low, width, height),
+        EncodingMode::Mode2 => xor_planes(&planes.low, &planes.high),
+        EncodingMode::Mode3 => delta_encode(&xor_planes(&planes.low, &planes.high), width, height),
+    };
+    let (first, second) = if primary_buffer == 0 {(buffer_b, buffer_c)} else {(buffer_c, buffer_b)};
+
+    let mut writer = BitWriter::new();
+    writer.write_bits(width as usize, 4);
+    writer.write_bits(height as usize, 4);
+    writer.write_bit(primary_buffer);
+
+    compress_bitplane(&mut writer, &first, height);
+
+    // The encoding mode goes between the two bitplanes: 0 is mode 1, 10 is mode 2 and 11 is mode 3
+    match mode {
+        EncodingMode::Mode1 => writer.write_bits(0b0, 1),
+        EncodingMode::Mode2 => writer.write_bits(0b10, 2),
+        EncodingMode::Mode3 => writer.write_bits(0b11, 2),
+    }
+
+    compress_bitplane(&mut writer, &second, height);
+
+    Ok(writer.bytes)
+}
+
+/// Compresses a sprite with all six encoding mode and primary buffer combinations and keeps
+/// the shortest one, like the compressor of the original games did.
+///
+/// The chosen output is decompressed again and compared with the input, so it is guaranteed
+/// to round-trip. On a tie the first combination tried wins.
+pub fn encode_optimal(planes: &Bitplanes) -> Result<OptimalEncoding, EncodeError> {
+    let mut candidates = Vec::new();
+    let mut outputs = Vec::new();
+
+    for mode in &[EncodingMode::Mode1, EncodingMode::Mode2, EncodingMode::Mode3] {
+        for primary_buffer in 0..2 {
+            let bytes = compress(planes, *mode, primary_buffer)?;
+            candidates.push(Candidate { mode: *mode, primary_buffer, size: bytes.len() });
+            outputs.push(bytes);
+        }
+    }
+
+    // Go from the smallest to the biggest until one of them decodes back to the same bitplanes
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by_key(|index| candidates[*index].size);
+    for index in order {
+        let decoded = decompress_bitplanes(&outputs[index]);
+        if let Ok(decoded) = decoded {
+            if decoded == *planes {
+                let candidate = candidates[index];
+                return Ok(OptimalEncoding {
+                    bytes: outputs.swap_remove(index),
+                    mode: candidate.mode,
+                    primary_buffer: candidate.primary_buffer,
+                    candidates,
+                });
+            }
+        }
+    }
+
+    Err(EncodeError::RoundTripMismatch)
+}
+
+// The decoder writes the pairs of bits going down each column of pairs, so we read them
+// in the same order: every tile column has 4 columns of pairs, each one `height * 8` rows long
+fn bitplane_pairs(plane: &[u8], height: u8) -> Vec<u8> {
+    let column_height = height as usize * 8;
+    let mut pairs = Vec::with_capacity(plane.len() * 4);
+
+    for column in plane.chunks(column_height) {
+        let mut bit_index = 0;
+        while bit_index < 8 {
+            for byte in column {
+                pairs.push((byte >> (6 - bit_index)) & 0b00000011);
+            }
+            bit_index += 2;
+        }
+    }
+
+    pairs
+}
+
+// Writes the initial packet bit followed by the RLE and data packets of the bitplane
+fn compress_bitplane(writer: &mut BitWriter, plane: &[u8], height: u8) {
+    let pairs = bitplane_pairs(plane, height);
+    // 0 for RLE packet and 1 for data packet
+    let mut reading_rle = pairs[0] == 0;
+    writer.write_bit(if reading_rle {0} else {1});
+
+    let mut index = 0;
+    while index < pairs.len() {
+        if reading_rle {
+            let mut zero_pairs = 0;
+            while index < pairs.len() && pairs[index] == 0 {
+                zero_pairs += 1;
+                index += 1;
+            }
+            write_rle_packet(writer, zero_pairs);
+            reading_rle = false;
+        } else {
+            while index < pairs.len() && pairs[index] != 0 {
+                writer.write_bits(pairs[index] as usize, 2);
+                index += 1;
+            }
+            // A data packet ends with a 00 pair, unless the bitplane is already complete
+            if index < pairs.len() {
+                writer.write_bits(0b00, 2);
+            }
+            reading_rle = true;
+        }
+    }
+}
+
+// The decoder reads a run of 1 bits ended by a 0 (L bits in total, worth 2^L - 2)
+// and then L more bits, the amount of zero pairs is the sum of both plus one.
+// So for N zero pairs, L is the biggest number such that 2^L <= N + 1
+fn write_rle_packet(writer: &mut BitWriter, zero_pairs: usize) {
+    let value = zero_pairs + 1;
+    let mut length = 1;
+    while (value >> (length + 1)) > 0 {
+        length += 1;
+    }
+    let prefix = (1 << length) - 2;
+    writer.write_bits(prefix, length);
+    writer.write_bits(value - (1 << length), length);
+}
+
+// The inverse of Buffer::delta_decode, each bit is stored as the difference with the pixel
+// to its left. The state is zero at the beginning of each row
+fn delta_encode(plane: &[u8], width: u8, height: u8) -> Vec<u8> {
+    let row_height = height as usize * 8;
+    let mut encoded = vec![0; plane.len()];
+
+    let mut row_index = 0;
+    while row_index < row_height {
+        let mut prev_state = 0;
+        let mut col_index = 0;
+
+        while col_index < width as usize {
+            let index = col_index * row_height + row_index;
+            let byte = plane[index];
+            encoded[index] = byte ^ ((byte >> 1) | (prev_state << 7));
+            prev_state = byte & 1;
+            col_index += 1;
+        }
+
+        row_index += 1;
+    }
+
+    encoded
+}
+
+fn xor_planes(plane: &[u8], other: &[u8]) -> Vec<u8> {
+    plane.iter().zip(other.iter()).map(|(a, b)| a ^ b).collect()
+}
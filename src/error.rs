@@ -0,0 +1,83 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while decompressing a sprite
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The compressed sprite could not be read
+    Io(io::Error),
+    /// The stream ended before the sprite was complete, the offset is the first bit that was missing
+    TruncatedStream { byte_offset: usize, bit_offset: u8 },
+    /// The header declares a width or height of zero or bigger than 7 tiles
+    InvalidDimensions { width: u8, height: u8 },
+    /// The decompressed data does not fit in the sprite buffers,
+    /// the offset is the bit of the stream that was being decoded
+    BufferOverflow { byte_offset: usize, bit_offset: u8 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Io(error) => write!(f, "could not read the compressed sprite: {}", error),
+            DecodeError::TruncatedStream { byte_offset, bit_offset } => write!(
+                f, "the compressed sprite ended unexpectedly at byte {} bit {}", byte_offset, bit_offset
+            ),
+            DecodeError::InvalidDimensions { width, height } => write!(
+                f, "invalid sprite dimensions {}x{} tiles", width, height
+            ),
+            DecodeError::BufferOverflow { byte_offset, bit_offset } => write!(
+                f, "the sprite overflows its buffer at byte {} bit {}", byte_offset, bit_offset
+            ),
+        }
+    }
+}
+
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DecodeError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(error: io::Error) -> DecodeError {
+        DecodeError::Io(error)
+    }
+}
+
+/// Everything that can go wrong while compressing a sprite
+#[derive(Debug)]
+pub enum EncodeError {
+    /// The width or height is zero or bigger than 7 tiles
+    InvalidDimensions { width: u8, height: u8 },
+    /// One of the bitplanes does not have `width * height * 8` bytes
+    PlaneSizeMismatch { expected: usize, found: usize },
+    /// The primary buffer is a single bit of the header, so it can only be 0 or 1
+    InvalidPrimaryBuffer { primary_buffer: u8 },
+    /// None of the compressed outputs decoded back to the original bitplanes
+    RoundTripMismatch,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::InvalidDimensions { width, height } => write!(
+                f, "invalid sprite dimensions {}x{} tiles", width, height
+            ),
+            EncodeError::PlaneSizeMismatch { expected, found } => write!(
+                f, "expected bitplanes of {} bytes but found {}", expected, found
+            ),
+            EncodeError::InvalidPrimaryBuffer { primary_buffer } => write!(
+                f, "invalid primary buffer {}, it must be 0 or 1", primary_buffer
+            ),
+            EncodeError::RoundTripMismatch => write!(
+                f, "the compressed sprite does not decode back to the original"
+            ),
+        }
+    }
+}
+
+impl Error for EncodeError {}
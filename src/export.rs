@@ -0,0 +1,40 @@
+use std::io::{self, Write};
+use std::num::NonZeroU32;
+
+use crate::palette::Palette;
+use crate::sprite::{Sprite, SPRITE_PIXELS};
+
+/// Writes the sprite as an indexed PNG with 4 colours, every pixel becomes a `scale * scale` square
+pub fn write_png<W: Write>(sprite: &Sprite, palette: &Palette, scale: NonZeroU32, writer: W) -> io::Result<()> {
+    let scale = scale.get();
+    let size = SPRITE_PIXELS as u32 * scale;
+
+    let mut encoder = png::Encoder::new(writer, size, size);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Two);
+    encoder.set_palette(palette.concat());
+    let mut png_writer = encoder.write_header().map_err(png_error)?;
+
+    // With 2 bits per pixel, every byte holds 4 pixels starting from the leftmost one
+    let row_length = (size as usize).div_ceil(4);
+    let mut data = Vec::with_capacity(row_length * size as usize);
+    for row in sprite.pixels.iter() {
+        let mut scaled_row = vec![0; row_length];
+        for (pixel_index, pixel) in row.iter().flat_map(|pixel| std::iter::repeat_n(pixel, scale as usize)).enumerate() {
+            scaled_row[pixel_index / 4] |= (pixel & 0b11) << (6 - (pixel_index % 4) * 2);
+        }
+        for _ in 0..scale {
+            data.extend_from_slice(&scaled_row);
+        }
+    }
+
+    png_writer.write_image_data(&data).map_err(png_error)?;
+    png_writer.finish().map_err(png_error)
+}
+
+pub(crate) fn png_error(error: png::EncodingError) -> io::Error {
+    match error {
+        png::EncodingError::IoError(error) => error,
+        error => io::Error::other(error),
+    }
+}
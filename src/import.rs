@@ -0,0 +1,210 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+
+use crate::sprite::{Bitplanes, SPRITE_PIXELS, SPRITE_TILES};
+
+/// How the colours of an image are turned into the four Game Boy shades
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMapping {
+    /// Use the palette index for indexed images with up to 4 colours, the luminance otherwise
+    Auto,
+    /// Palette index 0 is the lightest shade and 3 the darkest, only for indexed images
+    PaletteIndex,
+    /// The luminance of every pixel is rounded to the nearest of the four shades
+    Luminance,
+}
+
+/// Everything that can go wrong while importing an image
+#[derive(Debug)]
+pub enum ImportError {
+    /// The image could not be read
+    Io(io::Error),
+    /// The image is not a valid PNG
+    InvalidImage(String),
+    /// The image is not a multiple of 8 pixels or is bigger than 56x56 pixels
+    InvalidDimensions { width: u32, height: u32 },
+    /// Mapping by palette index was requested but the image is not indexed
+    NotIndexed,
+    /// A pixel uses a palette index that is not one of the four shades
+    PaletteIndexOutOfRange { index: u8 },
+    /// The 2bpp file is not a square of up to 7x7 tiles
+    InvalidTileCount { length: usize },
+    /// The 2bpp file does not have the number of tiles of the given dimensions
+    TileCountMismatch { length: usize, width: u8, height: u8 },
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(error) => write!(f, "could not read the image: {}", error),
+            ImportError::InvalidImage(error) => write!(f, "invalid PNG image: {}", error),
+            ImportError::InvalidDimensions { width, height } => write!(
+                f, "the image is {}x{} pixels, it must be a multiple of 8 and at most {}x{}",
+                width, height, SPRITE_PIXELS, SPRITE_PIXELS
+            ),
+            ImportError::NotIndexed => write!(f, "the image does not have a palette"),
+            ImportError::PaletteIndexOutOfRange { index } => write!(
+                f, "palette index {} is not one of the four shades", index
+            ),
+            ImportError::InvalidTileCount { length } => write!(
+                f, "{} bytes of 2bpp tiles are not a square of at most {}x{} tiles", length, SPRITE_TILES, SPRITE_TILES
+            ),
+            ImportError::TileCountMismatch { length, width, height } => write!(
+                f, "{} bytes of 2bpp tiles are not {}x{} tiles of 16 bytes", length, width, height
+            ),
+        }
+    }
+}
+
+impl Error for ImportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImportError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImportError {
+    fn from(error: io::Error) -> ImportError {
+        ImportError::Io(error)
+    }
+}
+
+impl From<png::DecodingError> for ImportError {
+    fn from(error: png::DecodingError) -> ImportError {
+        match error {
+            png::DecodingError::IoError(error) => ImportError::Io(error),
+            error => ImportError::InvalidImage(error.to_string()),
+        }
+    }
+}
+
+/// Reads a PNG and turns it into the two bitplanes that `compress` takes.
+///
+/// The image must be at most 56x56 pixels and both dimensions must be multiples of 8,
+/// so the sprite is `width / 8` by `height / 8` tiles.
+pub fn read_png<R: Read>(mut reader: R, mapping: ColorMapping) -> Result<Bitplanes, ImportError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    // Look at the header first to know how the image has to be decoded
+    let decoder = png::Decoder::new(&data[..]);
+    let header = decoder.read_info()?;
+    let info = header.info();
+    let (width, height) = (info.width, info.height);
+    if width == 0 || height == 0 || width % 8 != 0 || height % 8 != 0
+        || width as usize > SPRITE_PIXELS || height as usize > SPRITE_PIXELS {
+        return Err(ImportError::InvalidDimensions { width, height });
+    }
+    let indexed = info.color_type == png::ColorType::Indexed;
+    let small_palette = match &info.palette {
+        Some(palette) => palette.len() <= 4 * 3,
+        None => false,
+    };
+
+    let pixels = match mapping {
+        ColorMapping::PaletteIndex if !indexed => return Err(ImportError::NotIndexed),
+        ColorMapping::PaletteIndex => palette_indices(&data)?,
+        ColorMapping::Auto if indexed && small_palette => palette_indices(&data)?,
+        _ => luminance_shades(&data)?,
+    };
+
+    Ok(Bitplanes::from_pixels((width / 8) as u8, (height / 8) as u8, &pixels))
+}
+
+/// Reads raw 2bpp tiles laid out column by column, the way the pokered disassembly
+/// builds its sprites with `rgbgfx -Z` before `pkmncompress` turns them into `.pic` files.
+///
+/// The file has no header, so the width and height in tiles have to be given for sprites
+/// that are not square. Without them the sprite is taken as square, its size coming from
+/// the number of tiles.
+pub fn read_2bpp<R: Read>(mut reader: R, dimensions: Option<(u8, u8)>) -> Result<Bitplanes, ImportError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let (width, height) = match dimensions {
+        Some((width, height)) => {
+            if width == 0 || height == 0 || width as usize > SPRITE_TILES || height as usize > SPRITE_TILES {
+                return Err(ImportError::InvalidDimensions { width: width as u32 * 8, height: height as u32 * 8 });
+            }
+            if data.len() != width as usize * height as usize * 16 {
+                return Err(ImportError::TileCountMismatch { length: data.len(), width, height });
+            }
+            (width, height)
+        },
+        None => {
+            let tiles = data.len() / 16;
+            match (1..=SPRITE_TILES).find(|size| size * size == tiles && tiles * 16 == data.len()) {
+                Some(size) => (size as u8, size as u8),
+                None => return Err(ImportError::InvalidTileCount { length: data.len() }),
+            }
+        },
+    };
+
+    // Every row of a tile is the low byte and then the high byte, and the tiles
+    // are already in the same order as the bitplanes
+    Ok(Bitplanes {
+        width,
+        height,
+        low: data.iter().step_by(2).copied().collect(),
+        high: data.iter().skip(1).step_by(2).copied().collect(),
+    })
+}
+
+// Unpacks the palette index of every pixel, indexed images can have 1, 2, 4 or 8 bits per pixel
+fn palette_indices(data: &[u8]) -> Result<Vec<u8>, ImportError> {
+    let mut reader = png::Decoder::new(data).read_info()?;
+    let mut frame = vec![0; reader.output_buffer_size()];
+    let frame_info = reader.next_frame(&mut frame)?;
+    let bits_per_pixel = frame_info.bit_depth as usize;
+    let pixels_per_byte = 8 / bits_per_pixel;
+    let mask = ((1u16 << bits_per_pixel) - 1) as u8;
+
+    let mut pixels = Vec::with_capacity((frame_info.width * frame_info.height) as usize);
+    for row in frame.chunks(frame_info.line_size).take(frame_info.height as usize) {
+        for x in 0..frame_info.width as usize {
+            let shift = 8 - bits_per_pixel * (x % pixels_per_byte + 1);
+            let index = (row[x / pixels_per_byte] >> shift) & mask;
+            if index > 3 {
+                return Err(ImportError::PaletteIndexOutOfRange { index });
+            }
+            pixels.push(index);
+        }
+    }
+
+    Ok(pixels)
+}
+
+// Expands every pixel to 8 bit colour and rounds its luminance to one of the four shades,
+// transparent pixels are treated as the lightest shade
+fn luminance_shades(data: &[u8]) -> Result<Vec<u8>, ImportError> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut frame = vec![0; reader.output_buffer_size()];
+    let frame_info = reader.next_frame(&mut frame)?;
+    let samples = frame_info.color_type.samples();
+
+    let mut pixels = Vec::with_capacity((frame_info.width * frame_info.height) as usize);
+    for row in frame.chunks(frame_info.line_size).take(frame_info.height as usize) {
+        for pixel in row.chunks(samples).take(frame_info.width as usize) {
+            let (luminance, alpha) = match frame_info.color_type {
+                png::ColorType::Grayscale => (pixel[0] as u32, 255),
+                png::ColorType::GrayscaleAlpha => (pixel[0] as u32, pixel[1]),
+                png::ColorType::Rgba => (luminance(pixel), pixel[3]),
+                _ => (luminance(pixel), 255),
+            };
+            let shade = if alpha < 128 {0} else {3 - ((luminance + 42) / 85) as u8};
+            pixels.push(shade);
+        }
+    }
+
+    Ok(pixels)
+}
+
+// Rec. 601 luma, from 0 to 255
+fn luminance(pixel: &[u8]) -> u32 {
+    (pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000
+}
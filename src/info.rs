@@ -0,0 +1,108 @@
+use std::fmt;
+
+use crate::disasm::{Field, Instruction};
+use crate::EncodingMode;
+
+/// Everything the header and the packets of a compressed sprite say about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpriteInfo {
+    /// Width of the sprite in tiles
+    pub width: u8,
+    /// Height of the sprite in tiles
+    pub height: u8,
+    /// Offsets `allocate_space` computes to centre the sprite in the 7x7 tile box
+    pub vertical_offset: u8,
+    pub horizontal_offset: u8,
+    /// Which buffer the first bitplane goes to, 0 for buffer B and 1 for buffer C
+    pub primary_buffer: u8,
+    /// Type of the first packet of each bitplane, 0 for RLE and 1 for data
+    pub initial_packets: [u8; 2],
+    pub encoding_mode: EncodingMode,
+    /// How many bytes the compressed sprite takes
+    pub compressed_length: usize,
+    pub rle_packets: usize,
+    pub data_packets: usize,
+    /// Zero pairs written by the longest RLE packet
+    pub longest_run: usize,
+    /// Pairs written by the longest data packet
+    pub longest_data_packet: usize,
+}
+
+impl SpriteInfo {
+    // The header comes from the decoder, the packets from the disassembler
+    pub(crate) fn new(width: u8, height: u8, vertical_offset: u8, horizontal_offset: u8, compressed_length: usize, instructions: &[Instruction]) -> SpriteInfo {
+        let mut info = SpriteInfo {
+            width,
+            height,
+            vertical_offset,
+            horizontal_offset,
+            primary_buffer: 0,
+            initial_packets: [0; 2],
+            encoding_mode: EncodingMode::Mode1,
+            compressed_length,
+            rle_packets: 0,
+            data_packets: 0,
+            longest_run: 0,
+            longest_data_packet: 0,
+        };
+
+        let mut bitplane = 0;
+        for instruction in instructions {
+            match &instruction.field {
+                Field::PrimaryBuffer(buffer) => info.primary_buffer = *buffer,
+                Field::InitialPacket(packet) => {
+                    info.initial_packets[bitplane.min(1)] = *packet;
+                    bitplane += 1;
+                },
+                Field::EncodingMode(mode) => info.encoding_mode = *mode,
+                Field::RlePacket { zero_pairs, .. } => {
+                    info.rle_packets += 1;
+                    info.longest_run = info.longest_run.max(*zero_pairs);
+                },
+                Field::DataPacket { pairs, .. } => {
+                    info.data_packets += 1;
+                    info.longest_data_packet = info.longest_data_packet.max(pairs.len());
+                },
+                Field::Width(_) | Field::Height(_) => {},
+            }
+        }
+
+        info
+    }
+
+    /// The same fields as a JSON object, with the encoding mode as a number
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "width": self.width,
+            "height": self.height,
+            "vertical_offset": self.vertical_offset,
+            "horizontal_offset": self.horizontal_offset,
+            "primary_buffer": self.primary_buffer,
+            "initial_packets": self.initial_packets,
+            "encoding_mode": self.encoding_mode.number(),
+            "compressed_length": self.compressed_length,
+            "rle_packets": self.rle_packets,
+            "data_packets": self.data_packets,
+            "longest_run": self.longest_run,
+            "longest_data_packet": self.longest_data_packet,
+        }).to_string()
+    }
+}
+
+impl fmt::Display for SpriteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let packet = |packet: u8| if packet == 0 {"RLE"} else {"data"};
+        writeln!(f, "width:               {} tiles", self.width)?;
+        writeln!(f, "height:              {} tiles", self.height)?;
+        writeln!(f, "vertical_offset:     {}", self.vertical_offset)?;
+        writeln!(f, "horizontal_offset:   {}", self.horizontal_offset)?;
+        writeln!(f, "primary_buffer:      {}", if self.primary_buffer == 0 {"B"} else {"C"})?;
+        writeln!(f, "initial_packets:     {}, {}", packet(self.initial_packets[0]), packet(self.initial_packets[1]))?;
+        writeln!(f, "encoding_mode:       {}", self.encoding_mode.number())?;
+        writeln!(f, "compressed_length:   {} bytes", self.compressed_length)?;
+        writeln!(f, "rle_packets:         {}", self.rle_packets)?;
+        writeln!(f, "data_packets:        {}", self.data_packets)?;
+        writeln!(f, "longest_run:         {} zero pairs", self.longest_run)?;
+        write!(f, "longest_data_packet: {} pairs", self.longest_data_packet)
+    }
+}
@@ -0,0 +1,277 @@
+//! Decompressor and compressor for the 1st generation Pokémon sprite format.
+//!
+//! ```no_run
+//! let bytes = std::fs::read("who-is-that-pokemon.bin").unwrap();
+//! let sprite = pokemon_sprite_decompress::decompress(&bytes).unwrap();
+//! println!("{}x{} tiles", sprite.width, sprite.height);
+//! ```
+
+mod bitstream;
+mod buffer;
+mod disasm;
+mod encoder;
+mod error;
+mod export;
+mod import;
+mod info;
+mod palette;
+pub mod rom;
+mod snapshot;
+mod sprite;
+mod terminal;
+mod tiles;
+
+use std::fs;
+use std::path::Path;
+
+use crate::bitstream::BitStream;
+use crate::buffer::Buffer;
+
+pub use crate::disasm::{disassemble, Field, Instruction};
+pub use crate::encoder::{compress, encode_optimal, Candidate, OptimalEncoding};
+pub use crate::error::{DecodeError, EncodeError};
+pub use crate::export::write_png;
+pub use crate::import::{read_2bpp, read_png, ColorMapping, ImportError};
+pub use crate::info::SpriteInfo;
+pub use crate::palette::{
+    from_rgb555, load_palette, named_palette, palette_names, parse_palette, read_palette_file, MonPalette, Palette, PaletteError,
+    DEFAULT_PALETTE, DMG_PALETTE, GRAYSCALE_PALETTE,
+};
+pub use crate::snapshot::{write_contact_sheet, Snapshot, Stage};
+pub use crate::sprite::{Bitplanes, Sprite, SPRITE_PIXELS, SPRITE_TILES};
+pub use crate::terminal::{write_half_blocks, write_kitty, write_sixel, write_text, ColorDepth, TextStyle};
+pub use crate::tiles::{bitplane_tiles, sprite_tiles, tilemap};
+
+/// How the two bitplanes were encoded before being compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingMode {
+    Mode1,
+    Mode2,
+    Mode3,
+}
+
+impl EncodingMode {
+    /// The number the mode goes by, 1, 2 or 3
+    pub fn number(self) -> u8 {
+        match self {
+            EncodingMode::Mode1 => 1,
+            EncodingMode::Mode2 => 2,
+            EncodingMode::Mode3 => 3,
+        }
+    }
+}
+
+/// Settings that change how a sprite is decompressed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// Emulate the buffers the way they are laid out in the game's SRAM, so glitched sprites
+    /// (like MissingNo.) overflow into the following buffers and show the same garbage the
+    /// hardware shows instead of failing. This also allows any width and height from 0 to 15
+    pub glitch: bool,
+    /// Treat the sprite as a 4x4 tiles back sprite and scale it by two to fill the 7x7 tiles box
+    /// like the game does in battle, instead of centring it. Other sizes are rejected unless `glitch` is set
+    pub back: bool,
+    /// Mirror the sprite horizontally, the way the game shows the sprites that face right
+    pub flip: bool,
+    /// Keep the sprite's own `width * height` tiles in the top left corner of the 7x7 tiles box
+    /// instead of centring it
+    pub no_centring: bool,
+}
+
+/// Reads a compressed sprite from a file and decompresses it
+pub fn decompress_file<P: AsRef<Path>>(path: P) -> Result<Sprite, DecodeError> {
+    let bytes = fs::read(path)?;
+    decompress(&bytes)
+}
+
+/// Decompresses a sprite from its compressed bytes
+pub fn decompress(bytes: &[u8]) -> Result<Sprite, DecodeError> {
+    decompress_with_options(bytes, &DecodeOptions::default())
+}
+
+/// Decompresses a sprite from its compressed bytes with the given settings
+pub fn decompress_with_options(bytes: &[u8], options: &DecodeOptions) -> Result<Sprite, DecodeError> {
+    decompress_observed(bytes, options, &mut |_, _| {})
+}
+
+/// Decompresses a sprite and also returns a copy of the three buffers after every stage of the pipeline
+pub fn decompress_stages(bytes: &[u8], options: &DecodeOptions) -> Result<(Sprite, Vec<Snapshot>), DecodeError> {
+    let mut snapshots = Vec::new();
+    let sprite = decompress_observed(bytes, options, &mut |stage, buffer| snapshots.push(Snapshot::new(stage, &buffer.bytes)))?;
+    Ok((sprite, snapshots))
+}
+
+// Gets called with the buffer after every stage
+type Observer<'a> = &'a mut dyn FnMut(Stage, &Buffer);
+
+fn decompress_observed(bytes: &[u8], options: &DecodeOptions, observe: Observer) -> Result<Sprite, DecodeError> {
+    let decoded = decode(bytes, options, observe)?;
+    let mut buffer = decoded.buffer;
+
+    // Now we need to copy the content from buffer B to A and from C to B,
+    // but in the right order for the Gameboy to draw
+    if options.back {
+        // Only the 4x4 tiles of a back sprite fit in the box once doubled
+        if !options.glitch && (buffer.width, buffer.height) != (4, 4) {
+            return Err(DecodeError::InvalidDimensions { width: buffer.width, height: buffer.height });
+        }
+        buffer.scale_bitplane(1, 0);
+        buffer.scale_bitplane(2, 1);
+        finish_stage(Stage::ScaleByTwo, &buffer, observe);
+    } else {
+        buffer.copy_bitplane(1, 0);
+        buffer.copy_bitplane(2, 1);
+        finish_stage(Stage::CopyBitplane, &buffer, observe);
+    }
+
+    if options.flip {
+        buffer.flip_bitplane(0);
+        buffer.flip_bitplane(1);
+        finish_stage(Stage::Flip, &buffer, observe);
+    }
+
+    // Almost there!
+    // Now we need to zipper the buffer A and B into buffer C and B going backwards
+    buffer.zip_buffers();
+    finish_stage(Stage::Zip, &buffer, observe);
+
+    Ok(Sprite {
+        width: buffer.width,
+        height: buffer.height,
+        pixels: buffer.pixels(),
+        compressed_length: decoded.compressed_length,
+        encoding_mode: decoded.encoding_mode,
+        primary_buffer: decoded.primary_buffer,
+    })
+}
+
+/// Decompresses a sprite but stops before centring it, returning its two bitplanes
+pub fn decompress_bitplanes(bytes: &[u8]) -> Result<Bitplanes, DecodeError> {
+    let decoded = decode(bytes, &DecodeOptions::default(), &mut |_, _| {})?;
+    Ok(decoded.buffer.bitplanes())
+}
+
+/// Reads the header and the packets of a compressed sprite without drawing it
+pub fn sprite_info(bytes: &[u8], options: &DecodeOptions) -> Result<SpriteInfo, DecodeError> {
+    let decoded = decode(bytes, options, &mut |_, _| {})?;
+    let instructions = disassemble(bytes)?;
+    let buffer = &decoded.buffer;
+    Ok(SpriteInfo::new(
+        buffer.width, buffer.height, buffer.vertical_offset, buffer.horizontal_offset,
+        decoded.compressed_length, &instructions,
+    ))
+}
+
+// The buffer with both bitplanes decoded plus what we learned about the stream on the way
+struct Decoded {
+    buffer: Buffer,
+    compressed_length: usize,
+    encoding_mode: EncodingMode,
+    primary_buffer: u8,
+}
+
+// Decompresses both bitplanes and undoes the encoding, leaving them in buffers B and C
+fn decode(bytes: &[u8], options: &DecodeOptions, observe: Observer) -> Result<Decoded, DecodeError> {
+    let mut sprite_bytes = BitStream::new(bytes);
+    log::trace!("input bytes={:02X?}", sprite_bytes.bytes);
+
+    // Read the first byte:
+    // The first 4 bits are for the sprite width and the second 4 bits for the height
+
+    let sprite_width: u8 = sprite_bytes.read_bits(4, false)?; // Read next 4 bits
+    let sprite_height: u8 = sprite_bytes.read_bits(4, false)?; // Read next 4 bits
+
+    // The sprite has to fit in the 7 * 7 tiles box, unless we want to see what the glitches look like
+    let fits = sprite_width != 0 && sprite_height != 0 && sprite_width as usize <= SPRITE_TILES && sprite_height as usize <= SPRITE_TILES;
+    if !options.glitch && !fits {
+        return Err(DecodeError::InvalidDimensions { width: sprite_width, height: sprite_height });
+    }
+
+    // Width the width and height we can allocate the buffer
+    let mut buffer = Buffer::new();
+    buffer.glitch = options.glitch;
+    buffer.no_centring = options.no_centring;
+    buffer.allocate_space(sprite_width, sprite_height);
+
+    log::debug!(
+        "header width={} height={} vertical_offset={} horizontal_offset={}",
+        buffer.width, buffer.height, buffer.vertical_offset, buffer.horizontal_offset
+    );
+
+    // Primary buffer: this defines which bit buffer should be processed first
+    let primary_buffer: u8 = sprite_bytes.read_bits(1, false)?; // Read next 1 bit
+    log::debug!("header primary_buffer={}", primary_buffer);
+
+
+    // Initial packet type of the data
+    // 0 means RLE packet and 1 means data packet
+    let initial_packet: u8 = sprite_bytes.read_bits(1, false)?; // Read next 1 bit
+    log::debug!("header initial_packet={} bitplane_length={}", initial_packet, buffer.bitplane_length * 8);
+
+    buffer.decompress_to_bitplane(&mut sprite_bytes, initial_packet, primary_buffer == 0)?;
+    finish_stage(Stage::FirstDecompress, &buffer, observe);
+
+    let encoding_mode: EncodingMode = {
+        if sprite_bytes.current_bit()? == 0 {
+            sprite_bytes.next_bit();
+            EncodingMode::Mode1
+        } else {
+            sprite_bytes.next_bit();
+            match sprite_bytes.current_bit()? {
+                0 => {
+                    sprite_bytes.next_bit();
+                    EncodingMode::Mode2
+                },
+                _ => {
+                    sprite_bytes.next_bit();
+                    EncodingMode::Mode3
+                },
+            }
+        }
+    };
+
+    log::debug!("header encoding_mode={:?}", encoding_mode);
+    let initial_packet: u8 = sprite_bytes.read_bits(1, false)?; // Read next 1 bit
+    log::debug!("header initial_packet={}", initial_packet);
+    buffer.decompress_to_bitplane(&mut sprite_bytes, initial_packet, primary_buffer == 1)?;
+    finish_stage(Stage::SecondDecompress, &buffer, observe);
+
+    // In mode 1 and 3, we have to delta-decode the buffer C
+    // In any mode, we have to delta-decode the buffer B
+    // In mode 2 and 3, xor buffer C against buffer B
+
+    match encoding_mode {
+        EncodingMode::Mode1 => {
+            buffer.delta_decode(2);
+            buffer.delta_decode(1);
+            finish_stage(Stage::DeltaDecode, &buffer, observe);
+        },
+        EncodingMode::Mode2 => {
+            buffer.delta_decode(2);
+            finish_stage(Stage::DeltaDecode, &buffer, observe);
+            buffer.xor_buffers(2, 1);
+            finish_stage(Stage::Xor, &buffer, observe);
+        },
+        EncodingMode::Mode3 => {
+            buffer.delta_decode(2);
+            buffer.delta_decode(1);
+            finish_stage(Stage::DeltaDecode, &buffer, observe);
+            buffer.xor_buffers(2, 1);
+            finish_stage(Stage::Xor, &buffer, observe);
+        },
+    }
+    log::debug!("compressed_length={}", sprite_bytes.bytes_read());
+
+    Ok(Decoded {
+        buffer,
+        compressed_length: sprite_bytes.bytes_read(),
+        encoding_mode,
+        primary_buffer,
+    })
+}
+
+// Logs the buffers and hands them to whoever is watching
+fn finish_stage(stage: Stage, buffer: &Buffer, observe: Observer) {
+    buffer.trace_bitplanes(stage.name());
+    observe(stage, buffer);
+}
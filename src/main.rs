@@ -1,582 +1,841 @@
+use std::convert::TryFrom;
 use std::env;
-use std::cmp;
-use std::io::prelude::*;
-use std::fs::File;
-
-enum EncodingMode {
-    Mode1,
-    Mode2,
-    Mode3,
-}
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::process;
+
+use pokemon_sprite_decompress::rom::{self, Rom, RomError};
+use pokemon_sprite_decompress::{
+    bitplane_tiles, compress, decompress_bitplanes, decompress_stages, decompress_with_options, disassemble, encode_optimal, load_palette, read_2bpp, read_png, sprite_info,
+    sprite_tiles, tilemap, write_contact_sheet, write_half_blocks, write_kitty, write_png, write_sixel, write_text,
+    ColorDepth, ColorMapping, DecodeOptions, EncodingMode, Palette, Snapshot, Sprite, TextStyle, DEFAULT_PALETTE, SPRITE_TILES,
+};
+
+// Exit codes, so scripts can tell a bad sprite from a missing file
+const EXIT_INVALID_DATA: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_IO: i32 = 3;
+
+// Bigger scales only make huge images, at 16 the contact sheet of the stages is already 8000 pixels tall
+const MAX_SCALE: u32 = 16;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+const HELP: &str = "\
+Decompress and compress the sprites of the 1st generation Pokémon games
+
+Usage: pokemon_sprite <COMMAND> [OPTIONS]
+       pokemon_sprite [INPUT] [OPTIONS]     same as decode
+       pokemon_sprite --rom ROM [OPTIONS]   same as extract
+
+Commands:
+  decode   Decompress a sprite and draw it or save it as an image
+  encode   Compress a PNG into a sprite the game can load
+  info     Show the header and statistics of a compressed sprite
+  disasm   List every field of a compressed sprite with its bit offset
+  extract  Take sprites straight out of a Pokémon Red, Blue or Yellow ROM
+
+Run `pokemon_sprite <COMMAND> --help` for the options of every command.
+
+Options for every command:
+  -v, --verbose  Log what the decoder does to stderr, twice (or -vv) to also log every buffer
+  -h, --help     Show the help
+
+Exit codes:
+  0  Everything went fine
+  1  The sprite, image or ROM is not valid
+  2  The arguments are wrong
+  3  A file could not be read or written
+";
+
+const INPUT_HELP: &str = "\
+Input:
+  [INPUT]            File with the compressed sprite, stdin when missing or -
+  --offset N         Start reading at this byte of the input, also in hexadecimal as 0x...
+  --length N         Read at most this many bytes
+";
+
+const OUTPUT_HELP: &str = "\
+Output:
+  -o, --output FILE  Where to write the sprite, stdout when missing or -
+  --format FORMAT    png, blocks, ascii, ansi, sixel, kitty or 2bpp. By default PNG if
+                     there is an output file and blocks (half blocks in colour) on stdout
+  --palette PALETTE  A built-in palette (default, dmg, grayscale, sgb-red, gbc-red...)
+                     or a file with four colours, one #RRGGBB per line or JSON
+  --scale N          Make every pixel N times bigger in png, sixel and kitty, up to 16
+  --colors DEPTH     truecolor, 256 or 16 colours for blocks and ansi, detected by default
+  --crop             Only the tiles of the sprite itself instead of all 49, for 2bpp,
+                     not together with --back, --flip or --glitch
+  --tilemap FILE     Also save which tile goes where, one byte per tile row by row, for 2bpp
+";
+
+const DECODE_HELP: &str = "\
+Decompress a sprite and draw it or save it as an image
+
+Usage: pokemon_sprite decode [INPUT] [OPTIONS]
+
+Options:
+  --glitch           Emulate how glitched sprites overflow the game's buffers
+  --back             Scale a 4x4 tiles back sprite by two like the game does in battle
+  --flip             Mirror the sprite horizontally, like the game does for sprites facing right
+  --no-centring      Keep the sprite in the top left corner instead of centring it
+  --stages PATH      Also save the buffers after every step of the algorithm, as a
+                     contact sheet if PATH ends in .png or as a directory of images
+";
+
+const ENCODE_HELP: &str = "\
+Compress a PNG into a sprite the game can load
+
+Usage: pokemon_sprite encode [IMAGE] [OPTIONS]
+
+Options:
+  [IMAGE]                 PNG of up to 56x56 pixels, multiples of 8, or 2bpp tiles laid out
+                          column by column (rgbgfx -Z), stdin when missing or -
+  -o, --output FILE       Where to write the compressed sprite, stdout when missing or -
+  --mode N                Encoding mode 1, 2 or 3, by default the smallest one is used
+  --primary-buffer N      Which buffer goes first, 0 or 1, only with --mode
+  --mapping MAPPING       How colours become shades: auto, index or luminance
+  --width N, --height N   Size in tiles of 2bpp tiles, only needed if the sprite is not square
+  --pokered               Follow the conventions of the pokered disassembly: square sprites
+                          and shades by luminance
+";
+
+const INFO_HELP: &str = "\
+Show the header and statistics of a compressed sprite
+
+Usage: pokemon_sprite info [INPUT] [OPTIONS]
+
+Options:
+  --json             Print the information as a JSON object
+  --glitch           Emulate how glitched sprites overflow the game's buffers
+  --no-centring      Keep the sprite in the top left corner instead of centring it
+";
+
+const DISASM_HELP: &str = "\
+List every field of a compressed sprite with its bit offset
+
+Usage: pokemon_sprite disasm [INPUT] [OPTIONS]
+";
+
+const EXTRACT_HELP: &str = "\
+Take sprites straight out of a Pokémon Red, Blue or Yellow ROM
+
+Usage: pokemon_sprite extract ROM --species POKEDEX_NUMBER [--back] [OPTIONS]
+       pokemon_sprite extract ROM --dump DIRECTORY [OPTIONS]
+
+Options:
+  --species N        Pokédex number of the Pokémon, 0 for MissingNo.
+  --back             The back sprite instead of the front one, scaled by two like in battle
+  --flip             Mirror the sprite horizontally, like the game does for sprites facing right
+  --no-centring      Keep the sprite in the top left corner instead of centring it
+  --dump DIRECTORY   Save the front and back sprites of every Pokémon as PNGs plus a manifest.csv
+  --glitch           Emulate how glitched sprites overflow the game's buffers
+
+Sprites get the colours the game gives their species unless --palette is used.
+";
+
+// Everything the library logs goes to stderr as "LEVEL message", so the sprite on stdout
+// stays clean and the log can be parsed
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{} {}", record.level(), record.args());
+        }
+    }
 
-struct BitStream {
-    bit_index: u8,
-    byte_index: usize,
-    bytes: Vec<u8>,
-    last_two_bits: u8,
+    fn flush(&self) {}
 }
 
-const BUFFER_A_START: usize = 0;
-const BUFFER_A_END: usize = 391;
-const BUFFER_B_START: usize = 392;
-const BUFFER_B_END: usize = 783;
-const BUFFER_C_START: usize = 784;
-const BUFFER_C_END: usize = 1175;
-
-impl BitStream {
-    fn load_bytes_from_file(&mut self, filename: &str) {
-        let mut file = match File::open(&filename) {
-            Ok(file) => file,
-            Err(error) => panic!("Could not open the file! {:?}", error)
-        };
-        match file.read_to_end(&mut self.bytes) {
-            Ok(_) => println!("File data loaded!"),
-            Err(_) => panic!("An error ocurred trying to read the file"),
-        };
-    }
+static LOGGER: StderrLogger = StderrLogger;
 
-    fn next_bit(&mut self) {
-        self.bit_index += 1;
-        self.check_end_of_byte();
-        self.update_last_two_bits();
-    }
+// Why a command failed, each one has its own exit code
+enum Failure {
+    Usage(String),
+    InvalidData(String),
+    Io(String),
+    // Whoever reads stdout stopped early, like `head` does, which is not an error
+    BrokenPipe,
+}
 
-    fn current_byte(&self) -> u8 {
-        match self.bytes.get(self.byte_index) {
-            Some(byte) => *byte,
-            None => 0,
+impl Failure {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Failure::Usage(_) => EXIT_USAGE,
+            Failure::InvalidData(_) => EXIT_INVALID_DATA,
+            Failure::Io(_) => EXIT_IO,
+            Failure::BrokenPipe => 0,
         }
     }
+}
+
+// Adds what we were doing to an error, I/O errors are told apart from bad data by their source
+trait Context<T> {
+    fn context<S: AsRef<str>>(self, context: S) -> Result<T, Failure>;
+}
 
-    fn current_bit(&self) -> u8 {
-        (self.current_byte() >> 7 - self.bit_index) & 0b00000001
+impl<T, E: Into<Box<dyn Error>>> Context<T> for Result<T, E> {
+    fn context<S: AsRef<str>>(self, context: S) -> Result<T, Failure> {
+        self.map_err(|error| {
+            let error: Box<dyn Error> = error.into();
+            let message = format!("{}: {}", context.as_ref(), error);
+            let io_error = error.downcast_ref::<io::Error>()
+                .or_else(|| error.source().and_then(|source| source.downcast_ref::<io::Error>()));
+            match io_error {
+                Some(io_error) if io_error.kind() == io::ErrorKind::BrokenPipe => Failure::BrokenPipe,
+                Some(_) => Failure::Io(message),
+                None => Failure::InvalidData(message),
+            }
+        })
     }
+}
+
+// The arguments of a command, one at a time
+struct Arguments {
+    args: std::vec::IntoIter<String>,
+    verbosity: u8,
+}
 
-    fn update_last_two_bits(&mut self) {
-        self.last_two_bits = ((self.last_two_bits << 1) | self.current_bit()) & 0b00000011;
+impl Arguments {
+    fn new(args: Vec<String>) -> Arguments {
+        Arguments { args: args.into_iter(), verbosity: 0 }
     }
 
-    fn check_end_of_byte(&mut self) {
-        let last_byte_index = self.bytes.len() - 1;
-        if self.byte_index >= last_byte_index {
-            self.byte_index = last_byte_index;
-            if self.bit_index >= 7 {
-                self.bit_index = 7;
-            }
-        } else {
-            if self.bit_index > 7 {
-                self.bit_index = 0;
-                self.byte_index += 1;
-                if self.byte_index > last_byte_index {
-                    self.byte_index = last_byte_index;
-                }
+    // The verbosity works with every command, so it is taken out here, but not from the
+    // values of other options, which only `value` reads
+    fn next(&mut self) -> Option<String> {
+        loop {
+            let argument = self.args.next()?;
+            match argument.as_str() {
+                "-v" | "--verbose" => self.verbosity += 1,
+                "-vv" => self.verbosity += 2,
+                _ => return Some(argument),
             }
+            // Quiet by default, -v shows the header and what the decoder did, -vv every intermediate buffer
+            log::set_max_level(match self.verbosity {
+                0 => log::LevelFilter::Warn,
+                1 => log::LevelFilter::Debug,
+                _ => log::LevelFilter::Trace,
+            });
         }
     }
 
-    fn read_bits(&mut self, bits_amount: u8, write_from_left: bool) -> u8 {
-        let mut count: u8 = 0;
-        let mut byte: u8 = 0;
-
-        while count < bits_amount {
-            byte = match write_from_left {
-                true => byte | (self.current_bit() << 7 - count),
-                false => (byte << 1) | self.current_bit(),
-            };
+    // Puts the argument back in front, for when the command turns out to be the input
+    fn push_front(self, argument: String) -> Arguments {
+        let args: Vec<String> = std::iter::once(argument).chain(self.args).collect();
+        Arguments { args: args.into_iter(), verbosity: self.verbosity }
+    }
 
-            count += 1;
-            self.update_last_two_bits();
-            self.next_bit();
+    fn value(&mut self, option: &str) -> Result<String, Failure> {
+        match self.args.next() {
+            Some(value) => Ok(value),
+            None => Err(Failure::Usage(format!("{} needs a value", option))),
         }
-
-        byte
     }
 
-    fn bits_left(&self) -> usize {
-        (self.bytes.len() * 8) - ((self.byte_index * 8) + 1 + self.bit_index as usize) as usize
+    // Numbers can also be written in hexadecimal, since that's how offsets usually are
+    fn number<T: TryFrom<u64>>(&mut self, option: &str) -> Result<T, Failure> {
+        let value = self.value(option)?;
+        let number = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => value.parse::<u64>().ok(),
+        };
+        match number.and_then(|number| T::try_from(number).ok()) {
+            Some(number) => Ok(number),
+            None => Err(Failure::Usage(format!("invalid value {} for {}", value, option))),
+        }
     }
 }
 
-struct Buffer {
-    bit_index: u8,
-    width: u8,
-    height: u8,
-    vertical_offset: u8,
-    horizontal_offset: u8,
-    byte_index: usize,
-    bytes: Vec<u8>,
-    bitplane_length: usize,
-    row_index: usize,
+// Where the compressed bytes come from: a file or stdin, maybe only a part of it
+#[derive(Default)]
+struct Input {
+    path: Option<String>,
+    offset: usize,
+    length: Option<usize>,
 }
 
-impl Buffer {
-    // Buffer A => 0
-    // Buffer B => 1
-    // Buffer C => 2
-    fn get_buffer_address(buffer_number: u8) -> usize {
-        match buffer_number{
-            0 => BUFFER_A_START, // Address at 0
-            1 => BUFFER_B_START, // Address at 392
-            _ => BUFFER_C_START, // Address at 784
+impl Input {
+    // Takes the argument if it is one of the input options or the input itself
+    fn parse(&mut self, argument: &str, arguments: &mut Arguments) -> Result<bool, Failure> {
+        match argument {
+            "--offset" => self.offset = arguments.number("--offset")?,
+            "--length" => self.length = Some(arguments.number("--length")?),
+            _ => return positional(&mut self.path, argument),
         }
+        Ok(true)
     }
 
-    fn allocate_space(&mut self, width: u8, height: u8) {
-        const MAX_SPRITE_SIZE: u8 = 7; // 7 tiles
-        self.width = width;
-        self.height = height;
-        // We will need the vertical and horizontal offsets later, this is used to center the resulting
-        // sprite in a box of 7 * 7 tiles
-        // vertical offset = 7 - height
-        // horizontal offset = ((7 - width) / 2) + (1/2) -> then round the result down
-        self.vertical_offset = if width > MAX_SPRITE_SIZE {255 - width + MAX_SPRITE_SIZE} else {MAX_SPRITE_SIZE - width};
-        let height_substraction: u8 = if height > MAX_SPRITE_SIZE {255 - height + MAX_SPRITE_SIZE} else {MAX_SPRITE_SIZE - height};
-        let res = ((height_substraction as f32 / 2.0) + (1.0 / 2.0)) as f32;
-        self.horizontal_offset = res.floor() as u8;
-        // We need 3 bitplanes, the first and second ones are where the 
-        // decompressed bytes will be, which are 7 x 7 each.
-        // The third one is usually 7 x 7 maximum too, but glitched pokemon could
-        // have way more
-        // Note: Each tile has 64 pixels
-        self.bitplane_length = (7 * 7 * 2 * 8) + (cmp::max(7 * 7, width as usize * height as usize) * 8);
-        self.bytes = vec![0; self.bitplane_length];
-    }
-
-    fn write_pair(&mut self, data: u8) {
-        let column_height = self.height * 8;
-
-        self.bytes[self.byte_index] = self.bytes[self.byte_index] | (data << 8 - (self.bit_index + 2));
-
-        self.byte_index += 1;
-        self.row_index += 1;
-        // We have reached the end of the column
-        if self.row_index >= column_height as usize {
-            self.row_index = 0;
-            self.byte_index -= column_height as usize;
-            self.bit_index += 2;// Next column (in bits)
-            if self.bit_index >= 8 {
-                self.bit_index = 0;
-                // Jump to the next column
-                self.byte_index += column_height as usize;
-            }
+    fn name(&self) -> &str {
+        match self.path.as_deref() {
+            Some(path) if path != "-" => path,
+            _ => "stdin",
         }
     }
 
-    fn write_zero_pairs(&mut self, zero_pairs_amount: usize) {
-        let mut count = 0;
-
-        while count < zero_pairs_amount {
-            self.write_pair(0);
-            count += 1;
+    fn read(&self) -> Result<Vec<u8>, Failure> {
+        let bytes = read_input(self.path.as_deref(), self.name())?;
+        if self.offset > bytes.len() {
+            return Err(Failure::InvalidData(format!("The offset {:#X} is past the end of {} ({} bytes)", self.offset, self.name(), bytes.len())));
         }
+        let end = match self.length {
+            Some(length) => bytes.len().min(self.offset.saturating_add(length)),
+            None => bytes.len(),
+        };
+        log::info!("input={} offset={:#X} length={}", self.name(), self.offset, end - self.offset);
+        Ok(bytes[self.offset..end].to_vec())
     }
+}
 
-    fn decompress_to_bitplane(&mut self, bytes: &mut BitStream, initial_packet: u8, primary_buffer: bool) {
-        let mut rle_length: u8 = 0;
-        let mut reading_first_rle = initial_packet == 0; // 1 for data packet and 0 for RLE packet
-        let mut reading_second_rle = false;
-        let mut first_rle_bits_read: u16 = 0;
-        let mut bits_written: usize = 0;
-        let bits_to_write: usize = self.width as usize * self.height as usize * 8 * 8;
-        // If the primary buffer is true, start decoding into buffer B at location 392,
-        // else, decode into buffer C at location 784
-        // Glitched pokemons overflow from Buffer B to C
-        self.byte_index = if primary_buffer {BUFFER_B_START} else {BUFFER_C_START};
-        self.bit_index = 0;
-        self.row_index = 0;
-
-        println!("Bits to write: {}", bits_to_write);
-        while bits_written < bits_to_write {
-
-            if reading_first_rle {
-                let current_bit = bytes.current_bit();
-                rle_length += 1; // We have to count the length of the rle packet even if the bit is zero
-                if current_bit == 0 {
-                    // Once we find a zero, we can start reading the amount of bits we counted
-                    // If the bit is 1, we increment the count by 1 and jump to the next bit
-                    first_rle_bits_read = (first_rle_bits_read << 1) | (current_bit as u16);
-                    reading_first_rle = false;
-                    reading_second_rle = true;
-                } else {
-                    // If the bit is 1, we increment the count by 1 and jump to the next bit
-                    first_rle_bits_read = (first_rle_bits_read << 1) | (current_bit as u16);
-                }
-                bytes.next_bit();
-                continue;
-            }
-            if reading_second_rle {
-                // Read the amount of bits we counted
-                let mut second_rle_bits_read = 0;
-                let mut rle_bits_count = 0;
-                while rle_bits_count < rle_length {
-                    let current_bit = bytes.current_bit();
-                    second_rle_bits_read = (second_rle_bits_read << 1) | (current_bit as u16);
-                    rle_bits_count += 1;
-                    bytes.next_bit();
-                }
-                // Then, we add the first 2 groups plus one (the plus one is to take care of
-                // the "offset" of the compression algorithm)
-                // This is the amount to zero pairs that we have to add to the buffer.
-                // For example, if the result is 4, we will have to add 4 zero pairs,
-                // or 8 zeros in total
-                let zero_pairs = first_rle_bits_read + second_rle_bits_read + 1;
-                self.write_zero_pairs(zero_pairs as usize); // write the zero pairs to the buffer
-                bits_written += zero_pairs as usize * 2;
-                first_rle_bits_read = 0;
-                rle_length = 0;
-                // Aftrer reading RLE packets, the next is a data packet until we find a 00 pair
-                reading_first_rle = false;
-                reading_second_rle = false;
-                continue;
-            }
-            // When we are not reading RLE packets, we can read the pairs of data (data packets) until
-            // we find a 00 pair
-            if !reading_first_rle && !reading_second_rle {
-                let bits_pair = bytes.read_bits(2, false);
-                if bits_pair == 0 {
-                    rle_length = 0;
-                    reading_first_rle = true;
-                    reading_second_rle = false;
-                } else {
-                    self.write_pair(bits_pair); // write the zero pairs to the buffer
-                    bits_written += 2;
-                }
-            }
-        }
-        println!("Bytes written: {}", bits_written / 8);
-    }
-
-    fn delta_decode(&mut self, buffer_number: u8) {
-        let index_offset = Buffer::get_buffer_address(buffer_number);
-        let mut row_index: usize = 0;
-        let row_height = self.height as usize * 8; // Height in bits
-        let col_width = self.width as usize; // Width in bytes
-        // The initial state is always zero at the beginning of each row
-        let delta_decode_nibble: [u8; 16] = [
-            0b0000, 0b0001, 0b0011, 0b0010,
-            0b0111, 0b0110, 0b0100, 0b0101,
-            0b1111, 0b1110, 0b1100, 0b1101,
-            0b1000, 0b1001, 0b1011, 0b1010,
-        ];
-
-        // We have to process row by row, then pairs of 4 bits for each column
-        while row_index < row_height {
-            let mut prev_state = 0;
-            let mut col_index: usize = 0;
-
-            while col_index < col_width {
-                // Calculate the index in the bytes
-                let index: usize = (col_index * (self.height as usize * 8) + row_index) + index_offset;
-                let byte = self.bytes[index];
-
-                // Getting the first sub-column (4 bits)
-                let first = delta_decode_nibble[(byte >> 4) as usize] ^ (0b1111 * prev_state);
-                prev_state = first & 1;
-
-                // Then the second sub-column (4 bits)
-                let second = delta_decode_nibble[(byte & 0b1111) as usize] ^ (0b1111 * prev_state);
-                prev_state = second & 1;
-
-                // Combine the two
-                self.bytes[index] = (first << 4) + second;
-                col_index += 1;
+// The only positional argument of a command, anything else starting with - is an unknown option
+fn positional(value: &mut Option<String>, argument: &str) -> Result<bool, Failure> {
+    if argument.starts_with('-') && argument != "-" {
+        return Ok(false);
+    }
+    if value.is_some() {
+        return Err(Failure::Usage(format!("unexpected argument {}", argument)));
+    }
+    *value = Some(argument.to_string());
+    Ok(true)
+}
+
+// A file, or stdin for - or no file at all. Forgetting the input is a usage error,
+// reading from the terminal would only wait forever
+fn read_input(path: Option<&str>, name: &str) -> Result<Vec<u8>, Failure> {
+    match path {
+        Some(path) if path != "-" => fs::read(path).context(format!("Could not read {}", name)),
+        _ => {
+            if io::stdin().is_terminal() {
+                return Err(Failure::Usage("no input file and nothing piped to stdin".to_string()));
             }
+            let mut bytes = Vec::new();
+            io::stdin().lock().read_to_end(&mut bytes).context(format!("Could not read {}", name))?;
+            Ok(bytes)
+        },
+    }
+}
 
-            row_index += 1;
-            self.byte_index += 1;
-        }
+// A file, or stdout for - or no file at all
+fn create_output(path: Option<&str>) -> io::Result<Box<dyn Write>> {
+    match path {
+        Some(path) if path != "-" => Ok(Box::new(BufWriter::new(File::create(path)?))),
+        _ => Ok(Box::new(io::stdout().lock())),
     }
+}
 
-    fn xor_buffers(&mut self, buffer_index: u8, replace_buffer: u8) {
-        let buffer_index_offset = Buffer::get_buffer_address(buffer_index);
-        let replace_index_offset = Buffer::get_buffer_address(replace_buffer);
-        let end_index = 8 * 7 * 7;
-        let mut index = 0;
-        while index < end_index {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Png,
+    Blocks,
+    Ascii,
+    Ansi,
+    Sixel,
+    Kitty,
+    // Raw Game Boy tiles
+    TwoBpp,
+}
 
-            self.bytes[index + replace_index_offset] =
-                self.bytes[index + replace_index_offset] ^
-                self.bytes[index + buffer_index_offset];
+// Where and how the decoded sprite is written
+struct Output {
+    path: Option<String>,
+    format: Option<Format>,
+    palette: Option<Palette>,
+    scale: NonZeroU32,
+    colors: Option<ColorDepth>,
+    crop: bool,
+    tilemap: Option<String>,
+}
 
-            index += 1;
-        }
+impl Default for Output {
+    fn default() -> Output {
+        Output { path: None, format: None, palette: None, scale: NonZeroU32::MIN, colors: None, crop: false, tilemap: None }
     }
+}
 
-    fn wipe_bitplane(&mut self, buffer_number: u8) {
-        let offset = Buffer::get_buffer_address(buffer_number);
-
-        // Wipe the "to" bitplane first
-        let mut index = offset;
-        let buffer_size = 7 * 7 * 8;
-        while index < offset + buffer_size {
-            self.bytes[index] = 0;
-            index += 1;
+impl Output {
+    // Takes the argument if it is one of the output options
+    fn parse(&mut self, argument: &str, arguments: &mut Arguments) -> Result<bool, Failure> {
+        match argument {
+            "--output" | "-o" => self.path = Some(arguments.value(argument)?),
+            "--format" => {
+                self.format = Some(match arguments.value(argument)?.as_str() {
+                    "png" => Format::Png,
+                    "blocks" => Format::Blocks,
+                    "ascii" => Format::Ascii,
+                    "ansi" => Format::Ansi,
+                    "sixel" => Format::Sixel,
+                    "kitty" => Format::Kitty,
+                    "2bpp" => Format::TwoBpp,
+                    format => return Err(Failure::Usage(format!("unknown format {}", format))),
+                });
+            },
+            "--palette" => {
+                let name = arguments.value(argument)?;
+                self.palette = Some(load_palette(&name).context(format!("Could not load the palette {}", name))?);
+            },
+            "--scale" => {
+                self.scale = match NonZeroU32::new(arguments.number(argument)?) {
+                    Some(scale) if scale.get() <= MAX_SCALE => scale,
+                    _ => return Err(Failure::Usage(format!("the scale must be between 1 and {}", MAX_SCALE))),
+                };
+            },
+            "--colors" => {
+                self.colors = Some(match arguments.value(argument)?.as_str() {
+                    "truecolor" => ColorDepth::TrueColor,
+                    "256" => ColorDepth::Ansi256,
+                    "16" => ColorDepth::Ansi16,
+                    colors => return Err(Failure::Usage(format!("unknown colour depth {}", colors))),
+                });
+            },
+            "--crop" => self.crop = true,
+            "--tilemap" => self.tilemap = Some(arguments.value(argument)?),
+            _ => return Ok(false),
         }
+        Ok(true)
     }
 
-    fn copy_bitplane(&mut self, from: u8, to: u8) {
+    fn to_stdout(&self) -> bool {
+        matches!(self.path.as_deref(), None | Some("-"))
+    }
 
-        self.wipe_bitplane(to);
+    // A palette picked by hand wins over the one of the species
+    fn palette(&self, species_palette: Option<Palette>) -> Palette {
+        self.palette.or(species_palette).unwrap_or(DEFAULT_PALETTE)
+    }
 
-        let to_bitplane_start = Buffer::get_buffer_address(to);
-        let from_bitplane_start = Buffer::get_buffer_address(from);
-        let mut from_bitplane_index = from_bitplane_start;
+    // The compressed bytes are only decoded again for the tiles of --crop
+    fn write(&self, sprite: &Sprite, bytes: &[u8], options: &DecodeOptions, species_palette: Option<Palette>) -> Result<(), Failure> {
+        let format = match self.format {
+            Some(format) => format,
+            None if self.to_stdout() => Format::Blocks,
+            None => Format::Png,
+        };
+        if (self.crop || self.tilemap.is_some()) && format != Format::TwoBpp {
+            return Err(Failure::Usage("--crop and --tilemap only work with --format 2bpp".to_string()));
+        }
+        if matches!(format, Format::Png | Format::TwoBpp) && self.to_stdout() && io::stdout().is_terminal() {
+            return Err(Failure::Usage("not writing a binary file to the terminal, use --output".to_string()));
+        }
+        if format == Format::TwoBpp {
+            return self.write_tiles(sprite, bytes, options);
+        }
 
-        // Step 1: calculate the offset of the top-left corner
-        let mut index: usize = ((self.vertical_offset as usize * 8) + (self.horizontal_offset as usize * 8 * 7)) + to_bitplane_start;
+        let palette = self.palette(species_palette);
+        let destination = self.path.as_deref().filter(|path| *path != "-").unwrap_or("stdout");
+        let writer = create_output(self.path.as_deref()).context(format!("Could not write {}", destination))?;
+        let colors = || self.colors.unwrap_or_else(ColorDepth::detect);
+        let result = match format {
+            Format::Png => write_png(sprite, &palette, self.scale, writer),
+            Format::Blocks => write_half_blocks(sprite, &palette, colors(), writer),
+            Format::Ascii => write_text(sprite, &palette, TextStyle::Ascii, writer),
+            Format::Ansi => write_text(sprite, &palette, TextStyle::Ansi(colors()), writer),
+            Format::Sixel => write_sixel(sprite, &palette, self.scale, writer),
+            Format::Kitty => write_kitty(sprite, &palette, self.scale, writer),
+            Format::TwoBpp => unreachable!(),
+        };
+        result.context(format!("Could not write {}", destination))
+    }
 
-        // Step 2: copy the columns (height) of tiles
-        let height = self.height as usize * 8;
-        let mut current_column = 0;
-        while current_column < self.width {
-            let mut row_count: usize = 0;
-            while row_count < height {
-                self.bytes[index] = self.bytes[from_bitplane_index];
-                index += 1;
-                from_bitplane_index += 1;
-                row_count += 1;
-            }
+    fn write_tiles(&self, sprite: &Sprite, bytes: &[u8], options: &DecodeOptions) -> Result<(), Failure> {
+        // The cropped tiles are the bitplanes before they are placed in the box, so nothing
+        // that changes how they are placed can apply to them
+        if self.crop && (options.back || options.flip || options.glitch) {
+            return Err(Failure::Usage("--crop can't be used with --back, --flip or --glitch".to_string()));
+        }
+        let (tiles, width, height) = if self.crop {
+            let planes = decompress_bitplanes(bytes).context("Could not decompress the tiles of the sprite")?;
+            (bitplane_tiles(&planes), planes.width, planes.height)
+        } else {
+            (sprite_tiles(sprite), SPRITE_TILES as u8, SPRITE_TILES as u8)
+        };
 
-            // Revert the pointer back to the previous offset and add 56
-            // This will put the pointer to the next offset vertical offset
-            index -= height;
-            index += 56;
+        let destination = self.path.as_deref().filter(|path| *path != "-").unwrap_or("stdout");
+        let mut writer = create_output(self.path.as_deref()).context(format!("Could not write {}", destination))?;
+        writer.write_all(&tiles).and_then(|_| writer.flush()).context(format!("Could not write {}", destination))?;
 
-            current_column += 1;
+        if let Some(path) = &self.tilemap {
+            fs::write(path, tilemap(width, height)).context(format!("Could not write {}", path))?;
         }
+        Ok(())
     }
+}
 
-    fn zip_buffers(&mut self) {
-        let mut last_index_buffer_a: usize = BUFFER_A_END;
-        let mut last_index_buffer_b: usize = BUFFER_B_END;
-        let mut last_index_buffer_c: usize = BUFFER_C_END;
-
-        println!("last index A: {}", last_index_buffer_a);
-        println!("last index B: {}", last_index_buffer_b);
-        println!("last index C: {}", last_index_buffer_c);
+fn help(text: &str, sections: &[&str]) -> Result<(), Failure> {
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "{}", text).context("Could not write stdout")?;
+    for section in sections {
+        write!(stdout, "\n{}", section).context("Could not write stdout")?;
+    }
+    Ok(())
+}
 
-        loop {
-            self.bytes[last_index_buffer_c] = self.bytes[last_index_buffer_b];
-            last_index_buffer_c -= 1;
-            self.bytes[last_index_buffer_c] = self.bytes[last_index_buffer_a];
+fn unknown_option(argument: &str) -> Failure {
+    Failure::Usage(format!("unknown option {}", argument))
+}
 
-            if last_index_buffer_a == 0 {
-                break;
-            }
+fn decode(mut arguments: Arguments) -> Result<(), Failure> {
+    let mut input = Input::default();
+    let mut output = Output::default();
+    let mut options = DecodeOptions::default();
+    let mut stages: Option<String> = None;
 
-            last_index_buffer_a -= 1;
-            last_index_buffer_b -= 1;
-            last_index_buffer_c -= 1;
+    while let Some(argument) = arguments.next() {
+        if input.parse(&argument, &mut arguments)? || output.parse(&argument, &mut arguments)? {
+            continue;
+        }
+        match argument.as_str() {
+            "--glitch" => options.glitch = true,
+            "--no-centring" => options.no_centring = true,
+            "--back" => options.back = true,
+            "--flip" => options.flip = true,
+            "--stages" => stages = Some(arguments.value(&argument)?),
+            "-h" | "--help" => return help(DECODE_HELP, &[INPUT_HELP, OUTPUT_HELP]),
+            _ => return Err(unknown_option(&argument)),
         }
     }
 
-    fn render(&self) {
-        let mut pixels = Vec::<u8>::new(); // a "vram" to store each pixel
-        let mut index = BUFFER_B_START;
-
-        while index <= BUFFER_C_END {
-            let mut bit_index = 0;
+    let bytes = input.read()?;
+    let context = format!("Could not decompress {}", input.name());
+    let sprite = match stages {
+        Some(path) => {
+            let (sprite, snapshots) = decompress_stages(&bytes, &options).context(&context)?;
+            write_stages(&snapshots, &path, output.scale).context(format!("Could not write the stages to {}", path))?;
+            sprite
+        },
+        None => decompress_with_options(&bytes, &options).context(&context)?,
+    };
+    log::info!("compressed_length={}", sprite.compressed_length);
 
-            while bit_index <= 7 {
-                let bit_a = (self.bytes[index] >> (7 - bit_index)) & 0b00000001;
-                let bit_b = (self.bytes[index + 1] >> (7 - bit_index)) & 0b00000001;
+    output.write(&sprite, &bytes, &options, None)
+}
 
-                if bit_a == 0 && bit_b == 0 {
-                    pixels.push(0);
-                } else if bit_a == 0 && bit_b == 1 {
-                    pixels.push(1);
-                } else if bit_a == 1 && bit_b == 0 {
-                    pixels.push(2);
-                } else {
-                    pixels.push(3);
+fn encode(mut arguments: Arguments) -> Result<(), Failure> {
+    let mut image: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut mode: Option<EncodingMode> = None;
+    let mut primary_buffer: Option<u8> = None;
+    let mut mapping = ColorMapping::Auto;
+    let mut pokered = false;
+    let mut width: Option<u8> = None;
+    let mut height: Option<u8> = None;
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--output" | "-o" => output = Some(arguments.value(&argument)?),
+            "--mode" => {
+                mode = Some(match arguments.number::<u8>(&argument)? {
+                    1 => EncodingMode::Mode1,
+                    2 => EncodingMode::Mode2,
+                    3 => EncodingMode::Mode3,
+                    _ => return Err(Failure::Usage("the mode must be 1, 2 or 3".to_string())),
+                });
+            },
+            "--primary-buffer" => {
+                primary_buffer = match arguments.number(&argument)? {
+                    primary_buffer @ (0 | 1) => Some(primary_buffer),
+                    _ => return Err(Failure::Usage("the primary buffer must be 0 or 1".to_string())),
+                };
+            },
+            "--mapping" => {
+                mapping = match arguments.value(&argument)?.as_str() {
+                    "auto" => ColorMapping::Auto,
+                    "index" => ColorMapping::PaletteIndex,
+                    "luminance" => ColorMapping::Luminance,
+                    mapping => return Err(Failure::Usage(format!("unknown mapping {}", mapping))),
+                };
+            },
+            "--pokered" => pokered = true,
+            "--width" => width = Some(arguments.number(&argument)?),
+            "--height" => height = Some(arguments.number(&argument)?),
+            "-h" | "--help" => return help(ENCODE_HELP, &[]),
+            _ => {
+                if !positional(&mut image, &argument)? {
+                    return Err(unknown_option(&argument));
                 }
+            },
+        }
+    }
+    if primary_buffer.is_some() && mode.is_none() {
+        return Err(Failure::Usage("--primary-buffer needs --mode".to_string()));
+    }
+    let dimensions = match (width, height) {
+        (Some(width), Some(height)) => Some((width, height)),
+        (None, None) => None,
+        _ => return Err(Failure::Usage("--width and --height go together".to_string())),
+    };
+
+    let name = image.as_deref().filter(|path| *path != "-").unwrap_or("stdin");
+    let data = read_input(image.as_deref(), name)?;
+    // Anything that is not a PNG is taken as 2bpp tiles
+    let planes = if data.starts_with(PNG_SIGNATURE) {
+        if dimensions.is_some() {
+            return Err(Failure::Usage("--width and --height are only for 2bpp tiles, a PNG has its own size".to_string()));
+        }
+        // rgbgfx picks the shades of the disassembly's grey PNGs by how light they are
+        let mapping = if pokered {ColorMapping::Luminance} else {mapping};
+        read_png(data.as_slice(), mapping)
+    } else {
+        read_2bpp(data.as_slice(), dimensions)
+    };
+    let planes = planes.context(format!("Could not import {}", name))?;
+    if pokered && planes.width != planes.height {
+        return Err(Failure::InvalidData(format!(
+            "Could not import {}: pokered sprites are square but this one is {}x{} tiles", name, planes.width, planes.height
+        )));
+    }
 
-                bit_index += 1;
+    let bytes = match mode {
+        Some(mode) => compress(&planes, mode, primary_buffer.unwrap_or(0)).context(format!("Could not compress {}", name))?,
+        None => {
+            let encoding = encode_optimal(&planes).context(format!("Could not compress {}", name))?;
+            for candidate in &encoding.candidates {
+                log::debug!("candidate mode={:?} primary_buffer={} size={}", candidate.mode, candidate.primary_buffer, candidate.size);
             }
+            log::info!("chosen mode={:?} primary_buffer={} size={}", encoding.mode, encoding.primary_buffer, encoding.bytes.len());
+            encoding.bytes
+        },
+    };
+
+    let destination = output.as_deref().filter(|path| *path != "-").unwrap_or("stdout");
+    if destination == "stdout" && io::stdout().is_terminal() {
+        return Err(Failure::Usage("not writing a compressed sprite to the terminal, use --output".to_string()));
+    }
+    let mut writer = create_output(output.as_deref()).context(format!("Could not write {}", destination))?;
+    writer.write_all(&bytes).and_then(|_| writer.flush()).context(format!("Could not write {}", destination))
+}
+
+fn info(mut arguments: Arguments) -> Result<(), Failure> {
+    let mut input = Input::default();
+    let mut options = DecodeOptions::default();
+    let mut json = false;
 
-            index += 2;
+    while let Some(argument) = arguments.next() {
+        if input.parse(&argument, &mut arguments)? {
+            continue;
         }
+        match argument.as_str() {
+            "--json" => json = true,
+            "--glitch" => options.glitch = true,
+            "--no-centring" => options.no_centring = true,
+            "-h" | "--help" => return help(INFO_HELP, &[INPUT_HELP]),
+            _ => return Err(unknown_option(&argument)),
+        }
+    }
 
-        println!("{}", termion::clear::All);
-        let mut pixel_col = 0;
-        let mut pixel_row = 0;
-        let mut current_row_col = 0;
-        let pixel_height = 8 * 7;
-        for pixel in pixels {
+    let bytes = input.read()?;
+    let info = sprite_info(&bytes, &options).context(format!("Could not read {}", input.name()))?;
+    let text = if json {info.to_json()} else {info.to_string()};
+    writeln!(io::stdout().lock(), "{}", text).context("Could not write stdout")
+}
 
-            let coords = termion::cursor::Goto((pixel_col * 2) + 1, pixel_row + 1);
+fn disasm(mut arguments: Arguments) -> Result<(), Failure> {
+    let mut input = Input::default();
 
-            match pixel {
-                0 => print!("{goto}{color}  ", goto = coords, color = termion::color::Bg(termion::color::White)),
-                1 => print!("{goto}{color}  ", goto = coords, color = termion::color::Bg(termion::color::Blue)),
-                2 => print!("{goto}{color}  ", goto = coords, color = termion::color::Bg(termion::color::LightBlue)),
-                _ => print!("{goto}{color}  ", goto = coords, color = termion::color::Bg(termion::color::Black)),
-            }
-            
-            pixel_col += 1;
-            current_row_col += 1;
-            if current_row_col > 7 {
-                current_row_col = 0;
-                pixel_col -= 8;
-                pixel_row += 1;
-                if pixel_row >= pixel_height {
-                    pixel_row = 0;
-                    pixel_col += 8;
-                }
-            }
+    while let Some(argument) = arguments.next() {
+        if input.parse(&argument, &mut arguments)? {
+            continue;
         }
-        println!("{reset}", reset = termion::style::Reset);
-    }
-
-    fn render_bitplanes(&self) {
-        
-        println!("{}", termion::clear::All);
-        let pixel_height = 7 * 8;
-        let mut pixel_row = 0;
-        let mut pixel_col = 0;
-
-        for byte in &self.bytes[..BUFFER_C_END] {
-            let coords = termion::cursor::Goto(pixel_col + 1, pixel_row + 1);
-            let byte_string = format!("{:08b}", byte);
-            let new_string: String = byte_string.chars().map(|x| match x {
-                '0' => ' ',
-                _ => '@',
-            }).collect();
-            print!("{}{}", coords, new_string);
-            pixel_row += 1;
-            if pixel_row >= pixel_height {
-                pixel_row = 0;
-                pixel_col += 8;
-            }
+        match argument.as_str() {
+            "-h" | "--help" => return help(DISASM_HELP, &[INPUT_HELP]),
+            _ => return Err(unknown_option(&argument)),
         }
-        println!("");
     }
-}
 
-fn main() {
+    let bytes = input.read()?;
+    let instructions = disassemble(&bytes).context(format!("Could not disassemble {}", input.name()))?;
+    let mut stdout = io::stdout().lock();
+    for instruction in instructions {
+        writeln!(stdout, "{}", instruction).context("Could not write stdout")?;
+    }
+    Ok(())
+}
 
-    // Get the filename
-    let args: Vec<String> = env::args().collect();
+fn extract(mut arguments: Arguments) -> Result<(), Failure> {
+    let mut rom: Option<String> = None;
+    let mut species: Option<u8> = None;
+    let mut back = false;
+    let mut dump: Option<String> = None;
+    let mut output = Output::default();
+    let mut options = DecodeOptions::default();
+
+    while let Some(argument) = arguments.next() {
+        if output.parse(&argument, &mut arguments)? {
+            continue;
+        }
+        match argument.as_str() {
+            "--rom" => rom = Some(arguments.value(&argument)?),
+            "--species" => species = Some(arguments.number(&argument)?),
+            "--back" => {
+                back = true;
+                options.back = true;
+            },
+            "--flip" => options.flip = true,
+            "--dump" => dump = Some(arguments.value(&argument)?),
+            "--glitch" => options.glitch = true,
+            "--no-centring" => options.no_centring = true,
+            "-h" | "--help" => return help(EXTRACT_HELP, &[OUTPUT_HELP]),
+            _ => {
+                if !positional(&mut rom, &argument)? {
+                    return Err(unknown_option(&argument));
+                }
+            },
+        }
+    }
 
-    let filename = match &args.get(1) {
-        Some(filename) => filename.clone(),
-        None => panic!("No filename specified!"),
+    let filename = match rom {
+        Some(rom) => rom,
+        None => return Err(Failure::Usage("extract needs a ROM".to_string())),
     };
+    let rom = Rom::open(&filename).context(format!("Could not open {}", filename))?;
 
-    println!("Filename: {}", &filename);
+    if let Some(directory) = dump {
+        return dump_rom(&rom, &directory, &output, &options).context(format!("Could not dump the sprites from {} to {}", filename, directory));
+    }
 
-    let mut sprite_bytes = BitStream {
-        bit_index: 0,
-        byte_index: 0,
-        last_two_bits: 0,
-        bytes: Vec::new(),
+    let pokedex_number = match species {
+        Some(species) => species,
+        None => return Err(Failure::Usage("extract needs --species or --dump".to_string())),
     };
-    sprite_bytes.load_bytes_from_file(&filename);
-    println!("{:02X?}", sprite_bytes.bytes);
-
-    // Read the first byte:
-    // The first 4 bits are for the sprite width and the second 4 bits for the height
-
-    let sprite_width: u8 = sprite_bytes.read_bits(4, false); // Read next 4 bits
-    let sprite_height: u8 = sprite_bytes.read_bits(4, false); // Read next 4 bits
-
-    // Width the width and height we can allocate the buffer
-    let mut buffer = Buffer {
-        bit_index: 0,
-        byte_index: 0,
-        width: 0,
-        height: 0,
-        vertical_offset: 0,
-        horizontal_offset: 0,
-        bytes: Vec::new(),
-        bitplane_length: 0,
-        row_index: 0,
+    let index = match pokedex_number {
+        0 => rom::MISSINGNO,
+        _ => rom::index_from_pokedex(pokedex_number).ok_or(RomError::UnknownSpecies { pokedex_number })
+            .context(format!("Could not extract the sprite from {}", filename))?,
     };
-    buffer.allocate_space(sprite_width, sprite_height);
-
-    println!("Sprite width: {}", buffer.width);
-    println!("Sprite height: {}", buffer.height);
-    println!("Vertical offset: {}", buffer.vertical_offset);
-    println!("Horizontal offset: {}", buffer.horizontal_offset);
-
-    // Primary buffer: this defines which bit buffer should be processed first
-    let primary_buffer: u8 = sprite_bytes.read_bits(1, false); // Read next 1 bit
-    println!("Primary buffer: {}", primary_buffer);
-
-
-    // Initial packet type of the data
-    // 0 means RLE packet and 1 means data packet
-    let initial_packet: u8 = sprite_bytes.read_bits(1, false); // Read next 1 bit
-    println!("Initial packet: {}", initial_packet);
-    println!("Total bitplane length: {}", buffer.bitplane_length * 8);
-
-    println!("Starting to decompress the first buffer!");
-    buffer.decompress_to_bitplane(&mut sprite_bytes, initial_packet, primary_buffer == 0);
-    println!("First decompress result: ");
-    buffer.render_bitplanes();
-
-    let encoding_mode: EncodingMode = {
-        if sprite_bytes.current_bit() == 0 {
-            sprite_bytes.next_bit();
-            println!("Encoding mode 1");
-            EncodingMode::Mode1
-        } else {
-            sprite_bytes.next_bit();
-            match sprite_bytes.current_bit() {
-                0 => {
-                    println!("Encoding mode 2");
-                    sprite_bytes.next_bit();
-                    EncodingMode::Mode2
-                },
-                _ => {
-                    println!("Encoding mode 3");
-                    sprite_bytes.next_bit();
-                    EncodingMode::Mode3
+    let bytes = if back {rom.back_sprite(index)} else {rom.front_sprite(index)};
+    let bytes = bytes.context(format!("Could not extract the sprite from {}", filename))?;
+    let sprite = decompress_with_options(bytes, &options).context(format!("Could not decompress #{:03} from {}", pokedex_number, filename))?;
+    log::info!("compressed_length={}", sprite.compressed_length);
+
+    output.write(&sprite, bytes, &options, Some(rom_palette(&rom, pokedex_number)))
+}
+
+// Colours of the species in the ROM, the sprite is still worth showing if they can't be found
+fn rom_palette(rom: &Rom, pokedex_number: u8) -> Palette {
+    match rom.species_palette(pokedex_number) {
+        Ok(palette) => palette,
+        Err(error) => {
+            log::warn!("palette pokedex_number={} error=\"{}\"", pokedex_number, error);
+            DEFAULT_PALETTE
+        },
+    }
+}
+
+// Decompresses one sprite from the ROM and saves it as a PNG, returns where it was in the ROM
+fn dump_sprite(rom: &Rom, index: u8, back: bool, path: &Path, palette: Palette, output: &Output, options: &DecodeOptions) -> Result<(usize, Sprite), Box<dyn Error>> {
+    let pointers = rom.sprite_pointers(index)?;
+    let offset = if back {pointers.back} else {pointers.front};
+    let options = DecodeOptions { back, ..*options };
+    let sprite = decompress_with_options(if back {rom.back_sprite(index)?} else {rom.front_sprite(index)?}, &options)?;
+    let file = File::create(path)?;
+    let palette = output.palette(Some(palette));
+    write_png(&sprite, &palette, output.scale, BufWriter::new(file))?;
+    Ok((offset, sprite))
+}
+
+// Dumps the front and back sprites of MissingNo. and every Pokémon in Pokédex order,
+// a failure doesn't stop the dump, they are all listed at the end
+fn dump_rom(rom: &Rom, directory: &str, output: &Output, options: &DecodeOptions) -> Result<(), Box<dyn Error>> {
+    let directory = Path::new(directory);
+    fs::create_dir_all(directory)?;
+
+    let mut manifest = BufWriter::new(File::create(directory.join("manifest.csv"))?);
+    writeln!(manifest, "pokedex_number,index,side,file,rom_offset,compressed_size,width,height,encoding_mode,primary_buffer")?;
+
+    // A ROM without the palette tables would warn for every sprite, so they are looked up once
+    let palettes = match (0..=151).map(|pokedex_number| rom.species_palette(pokedex_number)).collect::<Result<Vec<_>, _>>() {
+        Ok(palettes) => palettes,
+        Err(error) => {
+            log::warn!("palette error=\"{}\"", error);
+            vec![DEFAULT_PALETTE; 152]
+        },
+    };
+
+    let mut dumped = 0;
+    let mut failures = Vec::new();
+    for pokedex_number in 0..=151 {
+        let index = match rom::index_from_pokedex(pokedex_number) {
+            Some(index) => index,
+            None => rom::MISSINGNO,
+        };
+        for side in &["front", "back"] {
+            let file_name = format!("{:03}-{}.png", pokedex_number, side);
+            match dump_sprite(rom, index, *side == "back", &directory.join(&file_name), palettes[pokedex_number as usize], output, options) {
+                Ok((offset, sprite)) => {
+                    writeln!(
+                        manifest, "{},{:#04X},{},{},{:#X},{},{},{},{},{}",
+                        pokedex_number, index, side, file_name, offset, sprite.compressed_length,
+                        sprite.width, sprite.height, sprite.encoding_mode.number(), sprite.primary_buffer
+                    )?;
+                    dumped += 1;
                 },
+                Err(error) => failures.push(format!("#{:03} {} (index {:#04X}): {}", pokedex_number, side, index, error)),
             }
         }
-    };
+    }
+    manifest.flush()?;
 
-    println!("Starting to decompress the second buffer!");
-    let initial_packet: u8 = sprite_bytes.read_bits(1, false); // Read next 1 bit
-    println!("Initial packet: {}", initial_packet);
-    buffer.decompress_to_bitplane(&mut sprite_bytes, initial_packet, primary_buffer == 1);
-    println!("Second decompress result: ");
-    buffer.render_bitplanes();
-
-    // In mode 1 and 3, we have to delta-decode the buffer C
-    // In any mode, we have to delta-decode the buffer B
-    // In mode 2 and 3, xor buffer C against buffer B
-
-    match encoding_mode {
-        EncodingMode::Mode1 => {
-            buffer.delta_decode(2);
-            buffer.delta_decode(1);
-        },
-        EncodingMode::Mode2 => {
-            buffer.delta_decode(2);
-            buffer.xor_buffers(2, 1);
-        },
-        EncodingMode::Mode3 => {
-            buffer.delta_decode(2);
-            buffer.delta_decode(1);
-            buffer.xor_buffers(2, 1);
-        },
+    eprintln!("Dumped {} sprites to {}, {} failed", dumped, directory.display(), failures.len());
+    for failure in failures {
+        eprintln!("  {}", failure);
     }
-    println!("Encoding result:");
-    buffer.render_bitplanes();
 
-    // Now we need to copy the content from buffer B to A and from C to B,
-    // but in the right order for the Gameboy to draw
-    buffer.copy_bitplane(1, 0);
-    buffer.copy_bitplane(2, 1);
+    Ok(())
+}
+
+// A path ending in .png gets every stage in a single contact sheet,
+// anything else is a directory with one numbered image per stage
+fn write_stages(snapshots: &[Snapshot], path: &str, scale: NonZeroU32) -> Result<(), Box<dyn Error>> {
+    if path.to_lowercase().ends_with(".png") {
+        write_contact_sheet(snapshots, scale, BufWriter::new(File::create(path)?))?;
+        return Ok(());
+    }
 
-    // Almost there!
-    // Now we need to zipper the buffer A and B into buffer C and B going backwards
-    buffer.zip_buffers();
-    println!("Resulting zip:");
+    let directory = Path::new(path);
+    fs::create_dir_all(directory)?;
+    for (index, snapshot) in snapshots.iter().enumerate() {
+        let file_name = format!("{}-{}.png", index + 1, snapshot.stage.name());
+        let file = File::create(directory.join(file_name))?;
+        write_contact_sheet(std::slice::from_ref(snapshot), scale, BufWriter::new(file))?;
+    }
+    Ok(())
+}
 
-    // And we can finally start rendering our sprite!!!
-    buffer.render();
+fn main() {
+    log::set_logger(&LOGGER).expect("the logger is only set once");
+    log::set_max_level(log::LevelFilter::Warn);
+
+    let mut rest = Arguments::new(env::args().skip(1).collect());
+    let command = rest.next();
+    let result = match command.as_deref() {
+        Some("decode") => decode(rest),
+        Some("encode") => encode(rest),
+        Some("info") => info(rest),
+        Some("disasm") => disasm(rest),
+        Some("extract") => extract(rest),
+        Some("help") | Some("-h") | Some("--help") => help(HELP, &[]),
+        // Piping a sprite in is enough to decode it, like it has always been
+        None if !io::stdin().is_terminal() => decode(rest),
+        None => Err(Failure::Usage("no command given".to_string())),
+        // Sprites used to be taken from a ROM without the extract command
+        Some("--rom") => extract(rest.push_front("--rom".to_string())),
+        // Anything else is the file to decode, like it has always been
+        Some(input) => decode(rest.push_front(input.to_string())),
+    };
 
+    if let Err(failure) = result {
+        match &failure {
+            Failure::Usage(message) => eprintln!("{}\nRun `pokemon_sprite --help` to see how to use it", message),
+            Failure::InvalidData(message) | Failure::Io(message) => eprintln!("{}", message),
+            Failure::BrokenPipe => {},
+        }
+        process::exit(failure.exit_code());
+    }
 }
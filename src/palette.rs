@@ -0,0 +1,267 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Four RGB colours, from the lightest to the darkest shade
+pub type Palette = [[u8; 3]; 4];
+
+/// The colours the terminal renderer has always used
+pub const DEFAULT_PALETTE: Palette = [
+    [0xFF, 0xFF, 0xFF], // White
+    [0x55, 0x55, 0xFF], // Light blue
+    [0x00, 0x00, 0xAA], // Blue
+    [0x00, 0x00, 0x00], // Black
+];
+
+/// The green screen of the original Game Boy
+pub const DMG_PALETTE: Palette = [
+    [0x9B, 0xBC, 0x0F],
+    [0x8B, 0xAC, 0x0F],
+    [0x30, 0x62, 0x30],
+    [0x0F, 0x38, 0x0F],
+];
+
+pub const GRAYSCALE_PALETTE: Palette = [
+    [0xFF, 0xFF, 0xFF],
+    [0xAA, 0xAA, 0xAA],
+    [0x55, 0x55, 0x55],
+    [0x00, 0x00, 0x00],
+];
+
+/// The palettes the games give to Pokémon sprites, every species uses one of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonPalette {
+    Mew,
+    Blue,
+    Red,
+    Cyan,
+    Purple,
+    Brown,
+    Green,
+    Pink,
+    Yellow,
+    Grey,
+}
+
+impl MonPalette {
+    /// In the same order as the palette IDs of the games, starting at `PAL_MEWMON`
+    pub const ALL: [MonPalette; 10] = [
+        MonPalette::Mew,
+        MonPalette::Blue,
+        MonPalette::Red,
+        MonPalette::Cyan,
+        MonPalette::Purple,
+        MonPalette::Brown,
+        MonPalette::Green,
+        MonPalette::Pink,
+        MonPalette::Yellow,
+        MonPalette::Grey,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MonPalette::Mew => "mew",
+            MonPalette::Blue => "blue",
+            MonPalette::Red => "red",
+            MonPalette::Cyan => "cyan",
+            MonPalette::Purple => "purple",
+            MonPalette::Brown => "brown",
+            MonPalette::Green => "green",
+            MonPalette::Pink => "pink",
+            MonPalette::Yellow => "yellow",
+            MonPalette::Grey => "grey",
+        }
+    }
+
+    /// Colours used on the Super Game Boy by Red and Blue
+    pub fn sgb(self) -> Palette {
+        let colors = match self {
+            MonPalette::Mew => [[31, 29, 31], [30, 30, 17], [21, 0, 4], [3, 2, 2]],
+            MonPalette::Blue => [[31, 29, 31], [18, 20, 27], [11, 15, 23], [3, 2, 2]],
+            MonPalette::Red => [[31, 29, 31], [31, 20, 10], [26, 10, 6], [3, 2, 2]],
+            MonPalette::Cyan => [[31, 29, 31], [21, 25, 29], [14, 19, 25], [3, 2, 2]],
+            MonPalette::Purple => [[31, 29, 31], [27, 22, 24], [21, 15, 23], [3, 2, 2]],
+            MonPalette::Brown => [[31, 29, 31], [28, 20, 15], [21, 14, 9], [3, 2, 2]],
+            MonPalette::Green => [[31, 29, 31], [20, 26, 16], [9, 20, 11], [3, 2, 2]],
+            MonPalette::Pink => [[31, 29, 31], [30, 22, 24], [28, 15, 21], [3, 2, 2]],
+            MonPalette::Yellow => [[31, 29, 31], [31, 28, 14], [26, 20, 0], [3, 2, 2]],
+            MonPalette::Grey => [[31, 29, 31], [26, 21, 22], [15, 15, 18], [3, 2, 2]],
+        };
+        from_rgb555(colors)
+    }
+
+    /// Colours used on the Game Boy Color by Yellow
+    pub fn gbc(self) -> Palette {
+        let colors = match self {
+            MonPalette::Mew => [[31, 31, 31], [31, 31, 0], [28, 12, 4], [0, 0, 0]],
+            MonPalette::Blue => [[31, 31, 31], [19, 20, 31], [10, 8, 20], [0, 0, 0]],
+            MonPalette::Red => [[31, 31, 31], [31, 21, 0], [29, 3, 2], [0, 0, 0]],
+            MonPalette::Cyan => [[31, 31, 31], [17, 26, 31], [1, 17, 26], [0, 0, 0]],
+            MonPalette::Purple => [[31, 31, 31], [30, 18, 26], [19, 9, 20], [0, 0, 0]],
+            MonPalette::Brown => [[31, 31, 31], [28, 20, 10], [16, 11, 3], [0, 0, 0]],
+            MonPalette::Green => [[31, 31, 31], [19, 31, 13], [6, 19, 1], [0, 0, 0]],
+            MonPalette::Pink => [[31, 31, 31], [31, 19, 27], [30, 6, 21], [0, 0, 0]],
+            MonPalette::Yellow => [[31, 31, 31], [31, 31, 0], [28, 18, 0], [0, 0, 0]],
+            MonPalette::Grey => [[31, 31, 31], [24, 21, 23], [13, 13, 16], [0, 0, 0]],
+        };
+        from_rgb555(colors)
+    }
+}
+
+/// Turns the 5 bit channels the Game Boy uses into 8 bit ones
+pub fn from_rgb555(colors: [[u8; 3]; 4]) -> Palette {
+    let mut palette = [[0; 3]; 4];
+    for (color, rgb555) in palette.iter_mut().zip(colors.iter()) {
+        for (channel, value) in color.iter_mut().zip(rgb555.iter()) {
+            *channel = (value << 3) | (value >> 2);
+        }
+    }
+    palette
+}
+
+/// Names accepted by `named_palette`
+pub fn palette_names() -> Vec<String> {
+    let mut names: Vec<String> = vec!["default".to_string(), "dmg".to_string(), "grayscale".to_string()];
+    names.extend(MonPalette::ALL.iter().map(|palette| format!("sgb-{}", palette.name())));
+    names.extend(MonPalette::ALL.iter().map(|palette| format!("gbc-{}", palette.name())));
+    names
+}
+
+/// Looks up a built-in palette: `default`, `dmg`, `grayscale`, `sgb-<colour>` for the Super Game Boy
+/// palettes of Red and Blue and `gbc-<colour>` for the Game Boy Color palettes of Yellow
+pub fn named_palette(name: &str) -> Option<Palette> {
+    let name = name.to_lowercase();
+    match name.as_str() {
+        "default" => return Some(DEFAULT_PALETTE),
+        "dmg" => return Some(DMG_PALETTE),
+        "grayscale" | "greyscale" => return Some(GRAYSCALE_PALETTE),
+        _ => {},
+    }
+
+    let (system, colour) = name.split_once('-')?;
+    let palette = MonPalette::ALL.iter().find(|palette| palette.name() == colour)?;
+    match system {
+        "sgb" => Some(palette.sgb()),
+        "gbc" => Some(palette.gbc()),
+        _ => None,
+    }
+}
+
+/// Everything that can go wrong while loading a palette
+#[derive(Debug)]
+pub enum PaletteError {
+    /// The palette file could not be read
+    Io(io::Error),
+    /// There is no built-in palette with that name
+    UnknownName(String),
+    /// A colour is not written as `#RRGGBB` or `[r, g, b]`
+    InvalidColor(String),
+    /// A palette needs exactly four colours
+    WrongColorCount { found: usize },
+    /// The file looks like JSON but could not be parsed
+    InvalidJson(String),
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaletteError::Io(error) => write!(f, "could not read the palette: {}", error),
+            PaletteError::UnknownName(name) => write!(f, "unknown palette {}, try one of {}", name, palette_names().join(", ")),
+            PaletteError::InvalidColor(color) => write!(f, "invalid colour {}", color),
+            PaletteError::WrongColorCount { found } => write!(f, "a palette needs 4 colours, found {}", found),
+            PaletteError::InvalidJson(error) => write!(f, "invalid JSON palette: {}", error),
+        }
+    }
+}
+
+impl Error for PaletteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PaletteError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PaletteError {
+    fn from(error: io::Error) -> PaletteError {
+        PaletteError::Io(error)
+    }
+}
+
+/// Reads a palette file, see `parse_palette` for the format
+pub fn read_palette_file<P: AsRef<Path>>(path: P) -> Result<Palette, PaletteError> {
+    parse_palette(&fs::read_to_string(path)?)
+}
+
+/// Parses four colours from the lightest to the darkest shade.
+///
+/// The text format has one `#RRGGBB` colour per line, anything after a `;` is a comment.
+/// The JSON format is a list of four colours, either `"#RRGGBB"` strings or `[r, g, b]` lists,
+/// optionally in the `"colors"` field of an object.
+pub fn parse_palette(text: &str) -> Result<Palette, PaletteError> {
+    let trimmed = text.trim_start();
+    let colors = if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        parse_json(trimmed)?
+    } else {
+        text.lines()
+            .map(|line| line.split(';').next().unwrap_or("").trim())
+            .filter(|line| !line.is_empty())
+            .map(parse_hex)
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    if colors.len() != 4 {
+        return Err(PaletteError::WrongColorCount { found: colors.len() });
+    }
+    let mut palette = [[0; 3]; 4];
+    palette.copy_from_slice(&colors);
+    Ok(palette)
+}
+
+fn parse_json(text: &str) -> Result<Vec<[u8; 3]>, PaletteError> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|error| PaletteError::InvalidJson(error.to_string()))?;
+    let value = match value.get("colors") {
+        Some(colors) => colors,
+        None => &value,
+    };
+    let colors = match value.as_array() {
+        Some(colors) => colors,
+        None => return Err(PaletteError::InvalidJson("expected a list of colours".to_string())),
+    };
+
+    colors.iter().map(|color| {
+        if let Some(hex) = color.as_str() {
+            return parse_hex(hex);
+        }
+        let channels: Option<Vec<u8>> = color.as_array().map(|channels| {
+            channels.iter().filter_map(|channel| channel.as_u64().filter(|channel| *channel <= 255).map(|channel| channel as u8)).collect()
+        });
+        match channels {
+            Some(channels) if channels.len() == 3 => Ok([channels[0], channels[1], channels[2]]),
+            _ => Err(PaletteError::InvalidColor(color.to_string())),
+        }
+    }).collect()
+}
+
+fn parse_hex(color: &str) -> Result<[u8; 3], PaletteError> {
+    let hex = color.trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|character| character.is_ascii_hexdigit()) {
+        return Err(PaletteError::InvalidColor(color.to_string()));
+    }
+    let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).unwrap_or(0);
+    Ok([channel(0), channel(2), channel(4)])
+}
+
+/// Picks a built-in palette by name, or reads a palette file if there is none with that name
+pub fn load_palette(name_or_path: &str) -> Result<Palette, PaletteError> {
+    if let Some(palette) = named_palette(name_or_path) {
+        return Ok(palette);
+    }
+    if !Path::new(name_or_path).exists() {
+        return Err(PaletteError::UnknownName(name_or_path.to_string()));
+    }
+    read_palette_file(name_or_path)
+}
@@ -0,0 +1,464 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::palette::{from_rgb555, Palette};
+
+// The title of the game is stored in the cartridge header
+const TITLE_START: usize = 0x134;
+const TITLE_END: usize = 0x144;
+
+// Every bank is 16 KiB and, except for bank 0, always mapped at 0x4000
+const BANK_SIZE: usize = 0x4000;
+
+// Each entry of the base stats table is 28 bytes long, the first byte is the Pokédex number,
+// the byte 10 has the sprite dimensions and the next 4 bytes are the front and back sprite pointers
+const BASE_STATS_ENTRY_LENGTH: usize = 28;
+const FRONT_SPRITE_POINTER: usize = 11;
+const BACK_SPRITE_POINTER: usize = 13;
+
+// Red and Blue don't have Mew in the base stats table, its entry lives in bank 1
+const MEW_BASE_STATS: usize = 0x425B;
+
+// Every species has a palette ID, one byte per Pokédex number starting with MissingNo.
+const MONSTER_PALETTES_LENGTH: usize = 152;
+// The palettes for Pokémon go from PAL_MEWMON to PAL_GREYMON
+const PAL_MEWMON: u8 = 0x10;
+const PAL_BLUEMON: u8 = 0x11;
+const PAL_REDMON: u8 = 0x12;
+const PAL_CYANMON: u8 = 0x13;
+const PAL_GREENMON: u8 = 0x16;
+const PAL_GREYMON: u8 = 0x19;
+// Every palette is 4 colours of 2 bytes, each one 5 bits per channel
+const PALETTE_LENGTH: usize = 8;
+
+/// The first of the many internal indexes that are MissingNo., they all share the same sprites
+pub const MISSINGNO: u8 = 0x1F;
+
+const MEW: u8 = 0x15;
+const FOSSIL_KABUTOPS: u8 = 0xB6;
+const FOSSIL_AERODACTYL: u8 = 0xB7;
+const MON_GHOST: u8 = 0xB8;
+
+// The games identify every species by an internal index which is not the Pokédex number.
+// This is the Pokédex number of every internal index starting from 1, MissingNo. are 0
+const POKEDEX_NUMBERS: [u8; 190] = [
+    112, 115, 32, 35, 21, 100, 34, 80, 2, 103, 108, 102, 88, 94, 29, 31, 104, 111, 131, 59,
+    151, 130, 90, 72, 92, 123, 120, 9, 127, 114, 0, 0, 58, 95, 22, 16, 79, 64, 75, 113,
+    67, 122, 106, 107, 24, 47, 54, 96, 76, 0, 126, 0, 125, 82, 109, 0, 56, 86, 50, 128,
+    0, 0, 0, 83, 48, 149, 0, 0, 0, 84, 60, 124, 146, 144, 145, 132, 52, 98, 0, 0,
+    0, 37, 38, 25, 26, 0, 0, 147, 148, 140, 141, 116, 117, 0, 0, 27, 28, 138, 139, 39,
+    40, 133, 136, 135, 134, 66, 41, 23, 46, 61, 62, 13, 14, 15, 0, 85, 57, 51, 49, 87,
+    0, 0, 10, 11, 12, 68, 0, 55, 97, 42, 150, 143, 129, 0, 0, 89, 0, 99, 91, 0,
+    101, 36, 110, 53, 105, 0, 93, 63, 65, 17, 18, 121, 1, 3, 73, 0, 118, 119, 0, 0,
+    0, 0, 77, 78, 19, 20, 33, 30, 74, 137, 142, 0, 81, 0, 0, 4, 7, 5, 8, 6,
+    0, 0, 0, 0, 43, 44, 45, 69, 70, 71,
+];
+
+/// The games this module knows how to read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Game {
+    Red,
+    Blue,
+    Yellow,
+}
+
+/// Everything that can go wrong while reading sprites from a ROM
+#[derive(Debug)]
+pub enum RomError {
+    /// The ROM could not be read
+    Io(io::Error),
+    /// The cartridge header is not one of Pokémon Red, Blue or Yellow
+    UnknownGame { title: String },
+    /// The base stats table could not be found in the ROM
+    BaseStatsNotFound,
+    /// There is no species with this Pokédex number
+    UnknownSpecies { pokedex_number: u8 },
+    /// The game loads this species' sprite from a hard-coded pointer instead of the base stats
+    UnsupportedSpecies { index: u8 },
+    /// The sprite pointer is outside of the ROM
+    InvalidPointer { offset: usize },
+    /// The species palettes or the palette colours could not be found in the ROM
+    PalettesNotFound,
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::Io(error) => write!(f, "could not read the ROM: {}", error),
+            RomError::UnknownGame { title } => write!(f, "unknown game {:?}, only Pokémon Red, Blue and Yellow are supported", title),
+            RomError::BaseStatsNotFound => write!(f, "could not find the base stats table in the ROM"),
+            RomError::UnknownSpecies { pokedex_number } => write!(f, "there is no Pokémon with the Pokédex number {}", pokedex_number),
+            RomError::UnsupportedSpecies { index } => write!(f, "the sprite of the species with index {:#04X} is not in the base stats table", index),
+            RomError::InvalidPointer { offset } => write!(f, "the sprite at {:#X} is outside of the ROM", offset),
+            RomError::PalettesNotFound => write!(f, "could not find the palette tables in the ROM"),
+        }
+    }
+}
+
+impl Error for RomError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RomError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(error: io::Error) -> RomError {
+        RomError::Io(error)
+    }
+}
+
+/// Where the compressed sprites of a species are in the ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpritePointers {
+    /// Bank where both sprites are stored
+    pub bank: u8,
+    /// Offset of the front sprite from the start of the ROM
+    pub front: usize,
+    /// Offset of the back sprite from the start of the ROM
+    pub back: usize,
+}
+
+/// Converts a Pokédex number into the internal index the games use
+pub fn index_from_pokedex(pokedex_number: u8) -> Option<u8> {
+    if pokedex_number == 0 {
+        return None;
+    }
+    POKEDEX_NUMBERS.iter().position(|number| *number == pokedex_number).map(|position| position as u8 + 1)
+}
+
+/// Converts an internal index into its Pokédex number, MissingNo. and any other glitch species are 0
+pub fn pokedex_from_index(index: u8) -> u8 {
+    match POKEDEX_NUMBERS.get((index as usize).wrapping_sub(1)) {
+        Some(number) => *number,
+        None => 0,
+    }
+}
+
+/// A Pokémon Red, Blue or Yellow ROM
+pub struct Rom {
+    bytes: Vec<u8>,
+    game: Game,
+    base_stats: usize,
+    mew_base_stats: Option<usize>,
+    monster_palettes: Option<usize>,
+    super_palettes: Option<usize>,
+}
+
+impl Rom {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Rom, RomError> {
+        Rom::new(fs::read(path)?)
+    }
+
+    pub fn new(bytes: Vec<u8>) -> Result<Rom, RomError> {
+        let title = match bytes.get(TITLE_START..TITLE_END) {
+            Some(title) => title.iter().take_while(|byte| byte.is_ascii_graphic() || **byte == b' ')
+                .map(|byte| *byte as char).collect(),
+            None => String::new(),
+        };
+        let game = match title.trim_end() {
+            "POKEMON RED" => Game::Red,
+            "POKEMON BLUE" => Game::Blue,
+            "POKEMON YELLOW" => Game::Yellow,
+            _ => return Err(RomError::UnknownGame { title }),
+        };
+
+        let base_stats = match find_base_stats(&bytes) {
+            Some(base_stats) => base_stats,
+            None => return Err(RomError::BaseStatsNotFound),
+        };
+        // Yellow has Mew at the end of the table, Red and Blue have it somewhere else
+        let mew_entry = base_stats + 150 * BASE_STATS_ENTRY_LENGTH;
+        let mew_base_stats = if bytes.get(mew_entry) == Some(&151) {
+            None
+        } else if bytes.get(MEW_BASE_STATS) == Some(&151) {
+            Some(MEW_BASE_STATS)
+        } else {
+            return Err(RomError::BaseStatsNotFound);
+        };
+
+        // Only the colours depend on these, so the sprites can still be read without them
+        let monster_palettes = find_monster_palettes(&bytes);
+        let super_palettes = find_super_palettes(&bytes);
+
+        Ok(Rom {
+            bytes,
+            game,
+            base_stats,
+            mew_base_stats,
+            monster_palettes,
+            super_palettes,
+        })
+    }
+
+    pub fn game(&self) -> Game {
+        self.game
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Finds the sprites of the species with the given internal index, the same way the game does.
+    ///
+    /// Glitch species are supported: just like in the game, the Pokédex number 0 of MissingNo.
+    /// wraps around to entry 255, so it reads whatever is 255 entries after the start of the
+    /// base stats table as if it was an entry.
+    pub fn sprite_pointers(&self, index: u8) -> Result<SpritePointers, RomError> {
+        if index == FOSSIL_KABUTOPS || index == FOSSIL_AERODACTYL || index == MON_GHOST {
+            return Err(RomError::UnsupportedSpecies { index });
+        }
+
+        let entry = match self.mew_base_stats {
+            Some(mew_base_stats) if index == MEW => mew_base_stats,
+            _ => {
+                // The game subtracts one from the Pokédex number without checking for 0
+                let position = pokedex_from_index(index).wrapping_sub(1) as usize;
+                self.base_stats + position * BASE_STATS_ENTRY_LENGTH
+            },
+        };
+        let front_pointer = self.read_pointer(entry + FRONT_SPRITE_POINTER)?;
+        let back_pointer = self.read_pointer(entry + BACK_SPRITE_POINTER)?;
+
+        let bank = sprite_bank(self.game, index);
+        Ok(SpritePointers {
+            bank,
+            front: rom_offset(bank, front_pointer),
+            back: rom_offset(bank, back_pointer),
+        })
+    }
+
+    /// The compressed front sprite of the species with the given internal index.
+    /// Sprites never cross banks, so the slice goes until the end of the bank
+    pub fn front_sprite(&self, index: u8) -> Result<&[u8], RomError> {
+        let pointers = self.sprite_pointers(index)?;
+        self.bank_slice(pointers.front)
+    }
+
+    /// The compressed back sprite of the species with the given internal index
+    pub fn back_sprite(&self, index: u8) -> Result<&[u8], RomError> {
+        let pointers = self.sprite_pointers(index)?;
+        self.bank_slice(pointers.back)
+    }
+
+    /// Colours the game uses for the species with the given Pokédex number (0 for MissingNo.)
+    /// on the Super Game Boy, read from the palette tables of the ROM
+    pub fn species_palette(&self, pokedex_number: u8) -> Result<Palette, RomError> {
+        if pokedex_number > 151 {
+            return Err(RomError::UnknownSpecies { pokedex_number });
+        }
+        let (monster_palettes, super_palettes) = match (self.monster_palettes, self.super_palettes) {
+            (Some(monster_palettes), Some(super_palettes)) => (monster_palettes, super_palettes),
+            _ => return Err(RomError::PalettesNotFound),
+        };
+
+        let palette_id = self.bytes[monster_palettes + pokedex_number as usize];
+        let offset = super_palettes + palette_id as usize * PALETTE_LENGTH;
+        match self.bytes.get(offset..offset + PALETTE_LENGTH) {
+            Some(colors) => Ok(from_rgb555(read_colors(colors))),
+            None => Err(RomError::PalettesNotFound),
+        }
+    }
+
+    fn read_pointer(&self, offset: usize) -> Result<u16, RomError> {
+        match self.bytes.get(offset..offset + 2) {
+            Some(pointer) => Ok(u16::from_le_bytes([pointer[0], pointer[1]])),
+            None => Err(RomError::InvalidPointer { offset }),
+        }
+    }
+
+    fn bank_slice(&self, offset: usize) -> Result<&[u8], RomError> {
+        let bank_end = (offset / BANK_SIZE + 1) * BANK_SIZE;
+        match self.bytes.get(offset..bank_end.min(self.bytes.len())) {
+            Some(slice) if !slice.is_empty() => Ok(slice),
+            _ => Err(RomError::InvalidPointer { offset }),
+        }
+    }
+}
+
+// The sprites are spread over 5 banks depending on the internal index:
+// index < $1F, bank $9
+// $1F <= index < $4A, bank $A
+// $4A <= index < $74, bank $B
+// $74 <= index < $99, bank $C
+// $99 <= index, bank $D
+// In Red and Blue, Mew is in bank $1 because it was added at the last minute
+fn sprite_bank(game: Game, index: u8) -> u8 {
+    if index == MEW && game != Game::Yellow {
+        return 0x1;
+    }
+    match index {
+        0x00..=0x1E => 0x9,
+        0x1F..=0x49 => 0xA,
+        0x4A..=0x73 => 0xB,
+        0x74..=0x98 => 0xC,
+        _ => 0xD,
+    }
+}
+
+// Pointers to banks other than 0 are always between 0x4000 and 0x7FFF
+fn rom_offset(bank: u8, pointer: u16) -> usize {
+    bank as usize * BANK_SIZE + (pointer as usize % BANK_SIZE)
+}
+
+// The base stats table is 150 entries in a row whose first byte is the Pokédex number,
+// instead of relying on a fixed address we look for that pattern
+fn find_base_stats(bytes: &[u8]) -> Option<usize> {
+    let table_length = 150 * BASE_STATS_ENTRY_LENGTH;
+    if bytes.len() < table_length {
+        return None;
+    }
+    (0..=bytes.len() - table_length).find(|start| {
+        (0..150).all(|position| bytes[start + position * BASE_STATS_ENTRY_LENGTH] as usize == position + 1)
+    })
+}
+
+// The palette ID of every species is a table of 152 bytes between PAL_MEWMON and PAL_GREYMON,
+// we look for it from the first starters: Bulbasaur is green, Charmander red and Squirtle cyan
+fn find_monster_palettes(bytes: &[u8]) -> Option<usize> {
+    let starters = [
+        PAL_GREENMON, PAL_GREENMON, PAL_GREENMON,
+        PAL_REDMON, PAL_REDMON, PAL_REDMON,
+        PAL_CYANMON, PAL_CYANMON, PAL_CYANMON,
+    ];
+    if bytes.len() < MONSTER_PALETTES_LENGTH {
+        return None;
+    }
+    (0..=bytes.len() - MONSTER_PALETTES_LENGTH).find(|start| {
+        bytes[start + 1..start + 1 + starters.len()] == starters
+            && bytes[*start..start + MONSTER_PALETTES_LENGTH].iter().all(|id| (PAL_MEWMON..=PAL_GREYMON).contains(id))
+    })
+}
+
+// The colours are in a table indexed by palette ID. The Pokémon palettes all go from a light
+// to a dark colour, and the blue, red and green ones are easy to tell apart by their middle colours
+fn find_super_palettes(bytes: &[u8]) -> Option<usize> {
+    let first = PAL_MEWMON as usize * PALETTE_LENGTH;
+    let length = (PAL_GREYMON - PAL_MEWMON + 1) as usize * PALETTE_LENGTH;
+    if bytes.len() < first + length {
+        return None;
+    }
+    (0..=bytes.len() - first - length).find(|start| {
+        let palette = |id: u8| read_colors(&bytes[start + id as usize * PALETTE_LENGTH..][..PALETTE_LENGTH]);
+        let dominant = |id: u8, channel: usize| {
+            let [_, light, dark, _] = palette(id);
+            (0..3).filter(|other| *other != channel).all(|other| light[channel] > light[other] || dark[channel] > dark[other])
+        };
+        (PAL_MEWMON..=PAL_GREYMON).all(|id| {
+            let [lightest, _, _, darkest] = palette(id);
+            lightest.iter().all(|channel| *channel >= 24) && darkest.iter().all(|channel| *channel <= 8)
+        }) && dominant(PAL_BLUEMON, 2) && dominant(PAL_REDMON, 0) && dominant(PAL_GREENMON, 1)
+    })
+}
+
+// Each colour is a little endian word with 5 bits for red, green and blue from the lowest bits
+fn read_colors(bytes: &[u8]) -> [[u8; 3]; 4] {
+    let mut colors = [[0; 3]; 4];
+    for (color, word) in colors.iter_mut().zip(bytes.chunks(2)) {
+        let word = u16::from_le_bytes([word[0], word[1]]);
+        *color = [(word & 0x1F) as u8, ((word >> 5) & 0x1F) as u8, ((word >> 10) & 0x1F) as u8];
+    }
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pokedex_numbers_round_trip() {
+        for pokedex_number in 1..=151 {
+            let index = index_from_pokedex(pokedex_number).unwrap();
+            assert_eq!(pokedex_from_index(index), pokedex_number);
+        }
+        assert_eq!(index_from_pokedex(0), None);
+        assert_eq!(index_from_pokedex(152), None);
+    }
+
+    #[test]
+    fn known_indexes() {
+        assert_eq!(index_from_pokedex(112), Some(0x01));
+        assert_eq!(index_from_pokedex(151), Some(MEW));
+        assert_eq!(index_from_pokedex(1), Some(0x99));
+        assert_eq!(pokedex_from_index(0x00), 0);
+        assert_eq!(pokedex_from_index(MISSINGNO), 0);
+        assert_eq!(pokedex_from_index(0xBE), 71);
+        assert_eq!(pokedex_from_index(0xBF), 0);
+        assert_eq!(pokedex_from_index(0xFF), 0);
+    }
+
+    #[test]
+    fn sprite_bank_boundaries() {
+        for game in [Game::Red, Game::Blue, Game::Yellow] {
+            assert_eq!(sprite_bank(game, 0x00), 0x9);
+            assert_eq!(sprite_bank(game, 0x1E), 0x9);
+            assert_eq!(sprite_bank(game, 0x1F), 0xA);
+            assert_eq!(sprite_bank(game, 0x49), 0xA);
+            assert_eq!(sprite_bank(game, 0x4A), 0xB);
+            assert_eq!(sprite_bank(game, 0x73), 0xB);
+            assert_eq!(sprite_bank(game, 0x74), 0xC);
+            assert_eq!(sprite_bank(game, 0x98), 0xC);
+            assert_eq!(sprite_bank(game, 0x99), 0xD);
+            assert_eq!(sprite_bank(game, 0xFF), 0xD);
+        }
+    }
+
+    #[test]
+    fn palette_tables_are_found_in_the_rom() {
+        let mut bytes = vec![0; 0x4000];
+
+        // A run of starters that has an ID out of range before the real table
+        let mut decoy = vec![PAL_MEWMON; MONSTER_PALETTES_LENGTH];
+        decoy[1..10].copy_from_slice(&[PAL_GREENMON, PAL_GREENMON, PAL_GREENMON, PAL_REDMON, PAL_REDMON, PAL_REDMON, PAL_CYANMON, PAL_CYANMON, PAL_CYANMON]);
+        let mut monster_palettes = decoy.clone();
+        decoy[100] = 0x1A;
+        monster_palettes[25] = 0x18;
+        bytes[0x1000..0x1000 + MONSTER_PALETTES_LENGTH].copy_from_slice(&decoy);
+        bytes[0x1234..0x1234 + MONSTER_PALETTES_LENGTH].copy_from_slice(&monster_palettes);
+        assert_eq!(find_monster_palettes(&bytes), Some(0x1234));
+
+        // The colours of Red and Blue from PAL_MEWMON to PAL_GREYMON, the IDs before them are left empty
+        let colors: [[[u16; 3]; 4]; 10] = [
+            [[31, 29, 31], [30, 30, 17], [21, 0, 4], [3, 2, 2]],
+            [[31, 29, 31], [18, 20, 27], [11, 15, 23], [3, 2, 2]],
+            [[31, 29, 31], [31, 20, 10], [26, 10, 6], [3, 2, 2]],
+            [[31, 29, 31], [21, 25, 29], [14, 19, 25], [3, 2, 2]],
+            [[31, 29, 31], [27, 22, 24], [21, 15, 23], [3, 2, 2]],
+            [[31, 29, 31], [28, 20, 15], [21, 14, 9], [3, 2, 2]],
+            [[31, 29, 31], [20, 26, 16], [9, 20, 11], [3, 2, 2]],
+            [[31, 29, 31], [30, 22, 24], [28, 15, 21], [3, 2, 2]],
+            [[31, 29, 31], [31, 28, 14], [26, 20, 0], [3, 2, 2]],
+            [[31, 29, 31], [26, 21, 22], [15, 15, 18], [3, 2, 2]],
+        ];
+        let super_palettes = 0x2000;
+        let words = colors.iter().flatten().map(|[red, green, blue]| red | green << 5 | blue << 10);
+        for (index, word) in words.enumerate() {
+            let offset = super_palettes + PAL_MEWMON as usize * PALETTE_LENGTH + index * 2;
+            bytes[offset..offset + 2].copy_from_slice(&word.to_le_bytes());
+        }
+        assert_eq!(find_super_palettes(&bytes), Some(super_palettes));
+        let red = super_palettes + PAL_REDMON as usize * PALETTE_LENGTH;
+        assert_eq!(read_colors(&bytes[red..red + PALETTE_LENGTH]), [[31, 29, 31], [31, 20, 10], [26, 10, 6], [3, 2, 2]]);
+
+        // Without the blue palette having the most blue, the table is not found
+        let blue = super_palettes + PAL_BLUEMON as usize * PALETTE_LENGTH;
+        bytes[blue + 2..blue + 6].copy_from_slice(&[0; 4]);
+        assert_eq!(find_super_palettes(&bytes), None);
+        assert_eq!(find_monster_palettes(&bytes[..0x1234]), None);
+    }
+
+    #[test]
+    fn mew_bank() {
+        assert_eq!(sprite_bank(Game::Red, MEW), 0x1);
+        assert_eq!(sprite_bank(Game::Blue, MEW), 0x1);
+        assert_eq!(sprite_bank(Game::Yellow, MEW), 0x9);
+        // Only Mew moved, its neighbours are still in bank $9
+        assert_eq!(sprite_bank(Game::Red, MEW - 1), 0x9);
+        assert_eq!(sprite_bank(Game::Red, MEW + 1), 0x9);
+    }
+}
@@ -0,0 +1,186 @@
+use std::io::{self, Write};
+use std::num::NonZeroU32;
+
+use crate::export::png_error;
+use crate::sprite::SPRITE_PIXELS;
+
+// Each buffer is 0x188 bytes, 7 columns of 56 bytes
+const BUFFER_LENGTH: usize = 0x188;
+const BUFFERS: usize = 3;
+
+// Layout of the contact sheet before scaling, in pixels
+const MARGIN: usize = 2;
+const PANEL_GAP: usize = 4;
+const LABEL_HEIGHT: usize = 5;
+const LABEL_GAP: usize = 2;
+const ROW_HEIGHT: usize = LABEL_HEIGHT + LABEL_GAP + SPRITE_PIXELS + MARGIN;
+
+// Palette indices of the contact sheet
+const CLEAR: u8 = 0;
+const SET: u8 = 1;
+const BACKGROUND: u8 = 2;
+const PALETTE: [[u8; 3]; 3] = [
+    [0xFF, 0xFF, 0xFF], // Bit is 0
+    [0x00, 0x00, 0x00], // Bit is 1, also used for the labels
+    [0xC0, 0xC0, 0xC0], // Around the buffers
+];
+
+/// A step of the decompression pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// The first bitplane was decompressed into its buffer
+    FirstDecompress,
+    /// The second bitplane was decompressed into the other buffer
+    SecondDecompress,
+    /// The bitplanes were delta-decoded
+    DeltaDecode,
+    /// Buffer C was XORed into buffer B, only in modes 2 and 3
+    Xor,
+    /// Both bitplanes were centred into buffers A and B
+    CopyBitplane,
+    /// Both bitplanes of a back sprite were scaled by two into buffers A and B instead
+    ScaleByTwo,
+    /// Buffers A and B were mirrored, only for flipped sprites
+    Flip,
+    /// Buffers A and B were interleaved into buffers B and C
+    Zip,
+}
+
+impl Stage {
+    /// Name of the stage, as used in the logs and file names
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::FirstDecompress => "first_decompress",
+            Stage::SecondDecompress => "second_decompress",
+            Stage::DeltaDecode => "delta_decode",
+            Stage::Xor => "xor",
+            Stage::CopyBitplane => "copy_bitplane",
+            Stage::ScaleByTwo => "scale_by_two",
+            Stage::Flip => "flip",
+            Stage::Zip => "zip",
+        }
+    }
+}
+
+/// The three 0x188 byte buffers (A, B and C, one after the other) right after a stage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub stage: Stage,
+    pub bytes: Vec<u8>,
+}
+
+impl Snapshot {
+    pub(crate) fn new(stage: Stage, bytes: &[u8]) -> Snapshot {
+        // In glitch mode the buffer is the whole SRAM, we only want to see the three buffers
+        Snapshot { stage, bytes: bytes[..BUFFERS * BUFFER_LENGTH].to_vec() }
+    }
+
+    /// Bit of the given buffer (0 for A, 1 for B, 2 for C) at the given coordinates.
+    /// Every 56 bytes are a column of 8 pixels wide, just like the tiles are laid out
+    pub fn bit(&self, buffer: usize, x: usize, y: usize) -> u8 {
+        let byte = self.bytes[buffer * BUFFER_LENGTH + (x / 8) * SPRITE_PIXELS + y];
+        (byte >> (7 - x % 8)) & 0b00000001
+    }
+}
+
+/// Writes the snapshots as a PNG, one row per stage with its name on top and buffers A, B and C
+/// from left to right, every pixel becomes a `scale * scale` square
+pub fn write_contact_sheet<W: Write>(snapshots: &[Snapshot], scale: NonZeroU32, writer: W) -> io::Result<()> {
+    let width = MARGIN * 2 + BUFFERS * SPRITE_PIXELS + (BUFFERS - 1) * PANEL_GAP;
+    let height = MARGIN + snapshots.len() * ROW_HEIGHT;
+    let mut sheet = vec![vec![BACKGROUND; width]; height];
+
+    for (row, snapshot) in snapshots.iter().enumerate() {
+        let top = MARGIN + row * ROW_HEIGHT;
+        let label = format!("{} {}", row + 1, snapshot.stage.name());
+        draw_label(&mut sheet, &label, MARGIN, top);
+
+        let panel_top = top + LABEL_HEIGHT + LABEL_GAP;
+        for buffer in 0..BUFFERS {
+            let panel_left = MARGIN + buffer * (SPRITE_PIXELS + PANEL_GAP);
+            for y in 0..SPRITE_PIXELS {
+                for x in 0..SPRITE_PIXELS {
+                    sheet[panel_top + y][panel_left + x] = if snapshot.bit(buffer, x, y) == 1 {SET} else {CLEAR};
+                }
+            }
+        }
+    }
+
+    let scale = scale.get() as usize;
+    let mut encoder = png::Encoder::new(writer, (width * scale) as u32, (height * scale) as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(PALETTE.concat());
+    let mut png_writer = encoder.write_header().map_err(png_error)?;
+
+    let mut data = Vec::with_capacity(width * height * scale * scale);
+    for row in sheet.iter() {
+        let scaled_row: Vec<u8> = row.iter().flat_map(|pixel| std::iter::repeat_n(*pixel, scale)).collect();
+        for _ in 0..scale {
+            data.extend_from_slice(&scaled_row);
+        }
+    }
+
+    png_writer.write_image_data(&data).map_err(png_error)?;
+    png_writer.finish().map_err(png_error)
+}
+
+// Draws the text in capitals with a 3x5 font, anything without a glyph is left blank
+fn draw_label(sheet: &mut [Vec<u8>], text: &str, left: usize, top: usize) {
+    for (index, character) in text.chars().enumerate() {
+        let glyph = glyph(character);
+        let glyph_left = left + index * 4;
+        for (y, glyph_row) in glyph.iter().enumerate() {
+            for x in 0..3 {
+                if (glyph_row >> (2 - x)) & 1 == 1 {
+                    if let Some(pixel) = sheet[top + y].get_mut(glyph_left + x) {
+                        *pixel = SET;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn glyph(character: char) -> [u8; 5] {
+    match character.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b010, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b110, 0b101, 0b010],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b010, 0b101, 0b010, 0b101, 0b010],
+        '9' => [0b010, 0b101, 0b011, 0b001, 0b110],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        _ => [0; 5],
+    }
+}
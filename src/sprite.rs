@@ -0,0 +1,95 @@
+use crate::EncodingMode;
+
+// Every sprite is drawn in a box of 7 * 7 tiles, 8 * 8 pixels each
+pub const SPRITE_TILES: usize = 7;
+pub const SPRITE_PIXELS: usize = SPRITE_TILES * 8;
+
+/// A decompressed sprite, already centred in its 56x56 pixel box.
+///
+/// Each pixel is a 2bpp Game Boy colour index: 0 is the lightest shade and 3 the darkest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sprite {
+    /// Width of the sprite in tiles, as read from the header
+    pub width: u8,
+    /// Height of the sprite in tiles, as read from the header
+    pub height: u8,
+    /// Pixel grid indexed as `pixels[row][column]`
+    pub pixels: [[u8; SPRITE_PIXELS]; SPRITE_PIXELS],
+    /// How many bytes of the input the compressed sprite took, the rest of the input was not read
+    pub compressed_length: usize,
+    /// How the bitplanes were encoded
+    pub encoding_mode: EncodingMode,
+    /// Which buffer the first bitplane was decompressed into, 0 for buffer B and 1 for buffer C
+    pub primary_buffer: u8,
+}
+
+impl Sprite {
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y][x]
+    }
+
+    /// Mirrors the sprite from left to right, like `DecodeOptions::flip` does while decoding
+    pub fn flip_horizontally(&mut self) {
+        for row in self.pixels.iter_mut() {
+            row.reverse();
+        }
+    }
+}
+
+/// The two bitplanes of a sprite before they are centred and interleaved.
+///
+/// Both planes are `width * height * 8` bytes long and stored the way the game decompresses them:
+/// one column of tiles after the other, each column being `height * 8` bytes from top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitplanes {
+    /// Width of the sprite in tiles
+    pub width: u8,
+    /// Height of the sprite in tiles
+    pub height: u8,
+    /// Low bit of every pixel, this is what ends up in buffer B
+    pub low: Vec<u8>,
+    /// High bit of every pixel, this is what ends up in buffer C
+    pub high: Vec<u8>,
+}
+
+impl Bitplanes {
+    /// Length in bytes that each plane must have for the given dimensions
+    pub fn plane_length(width: u8, height: u8) -> usize {
+        width as usize * height as usize * 8
+    }
+
+    /// Builds the bitplanes from a row-major grid of `width * 8` by `height * 8` 2bpp colours
+    pub fn from_pixels(width: u8, height: u8, pixels: &[u8]) -> Bitplanes {
+        let plane_length = Bitplanes::plane_length(width, height);
+        let pixel_width = width as usize * 8;
+        let mut planes = Bitplanes {
+            width,
+            height,
+            low: vec![0; plane_length],
+            high: vec![0; plane_length],
+        };
+
+        for (pixel_index, pixel) in pixels.iter().enumerate().take(plane_length * 8) {
+            let x = pixel_index % pixel_width;
+            let y = pixel_index / pixel_width;
+            let index = planes.index(x, y);
+            planes.low[index] |= (pixel & 0b01) << (7 - x % 8);
+            planes.high[index] |= ((pixel & 0b10) >> 1) << (7 - x % 8);
+        }
+
+        planes
+    }
+
+    /// Colour of the pixel at the given coordinates, counted from the top left corner of the sprite
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        let index = self.index(x, y);
+        let low_bit = (self.low[index] >> (7 - x % 8)) & 0b00000001;
+        let high_bit = (self.high[index] >> (7 - x % 8)) & 0b00000001;
+        (high_bit << 1) | low_bit
+    }
+
+    // Every column of tiles is stored one after the other
+    fn index(&self, x: usize, y: usize) -> usize {
+        (x / 8) * self.height as usize * 8 + y
+    }
+}
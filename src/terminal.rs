@@ -0,0 +1,256 @@
+use std::env;
+use std::io::{self, Write};
+use std::num::NonZeroU32;
+
+use crate::palette::Palette;
+use crate::sprite::{Sprite, SPRITE_PIXELS};
+
+// Upper half block, its foreground is the top pixel and its background the bottom one
+const HALF_BLOCK: char = '▀';
+const RESET: &str = "\x1b[0m";
+
+// Colours of the 16 ANSI colours as xterm draws them
+const ANSI_COLORS: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00], [0xCD, 0x00, 0x00], [0x00, 0xCD, 0x00], [0xCD, 0xCD, 0x00],
+    [0x00, 0x00, 0xEE], [0xCD, 0x00, 0xCD], [0x00, 0xCD, 0xCD], [0xE5, 0xE5, 0xE5],
+    [0x7F, 0x7F, 0x7F], [0xFF, 0x00, 0x00], [0x00, 0xFF, 0x00], [0xFF, 0xFF, 0x00],
+    [0x5C, 0x5C, 0xFF], [0xFF, 0x00, 0xFF], [0x00, 0xFF, 0xFF], [0xFF, 0xFF, 0xFF],
+];
+
+// Levels of every channel in the 6x6x6 colour cube of the 256 colours
+const CUBE_LEVELS: [u8; 6] = [0x00, 0x5F, 0x87, 0xAF, 0xD7, 0xFF];
+
+/// How many colours the terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// 24 bit colour, the palette is shown as it is
+    TrueColor,
+    /// The 256 colours of xterm, every colour is rounded to the nearest one
+    Ansi256,
+    /// The 16 basic ANSI colours
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// Guesses what the terminal supports from `COLORTERM` and `TERM`, the same way most tools do
+    pub fn detect() -> ColorDepth {
+        let color_term = env::var("COLORTERM").unwrap_or_default().to_lowercase();
+        let term = env::var("TERM").unwrap_or_default().to_lowercase();
+        if color_term == "truecolor" || color_term == "24bit" || term.ends_with("-direct") {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
+
+/// Draws the sprite with upper half blocks, two rows of pixels per line, so the whole
+/// sprite takes 56x28 cells. Nothing but colours is written, so it can go to any terminal or file
+pub fn write_half_blocks<W: Write>(sprite: &Sprite, palette: &Palette, depth: ColorDepth, mut writer: W) -> io::Result<()> {
+    for rows in sprite.pixels.chunks(2) {
+        let mut line = String::new();
+        // The colours only need to be set again when they change
+        let mut current = None;
+        for column in 0..SPRITE_PIXELS {
+            let top = palette[rows[0][column] as usize & 0b11];
+            let bottom = palette[rows[rows.len() - 1][column] as usize & 0b11];
+            if current != Some((top, bottom)) {
+                line.push_str(&escape_code(top, depth, false));
+                line.push_str(&escape_code(bottom, depth, true));
+                current = Some((top, bottom));
+            }
+            line.push(HALF_BLOCK);
+        }
+        writeln!(writer, "{}{}", line, RESET)?;
+    }
+    writer.flush()
+}
+
+// Characters for every shade in the plain text renderer, from the lightest to the darkest
+const SHADES: [char; 4] = [' ', '.', '+', '#'];
+
+/// How the plain text renderer draws every pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextStyle {
+    /// Shade characters only, no escape codes at all
+    Ascii,
+    /// Spaces with the background set to the colour of the pixel
+    Ansi(ColorDepth),
+}
+
+/// Writes the sprite one line per row of pixels, two characters per pixel so it keeps its proportions.
+/// It never moves the cursor or clears the screen, so it is fine for logs and pipes
+pub fn write_text<W: Write>(sprite: &Sprite, palette: &Palette, style: TextStyle, mut writer: W) -> io::Result<()> {
+    for row in sprite.pixels.iter() {
+        let mut line = String::new();
+        let mut current = None;
+        for pixel in row.iter() {
+            let shade = *pixel as usize & 0b11;
+            match style {
+                TextStyle::Ascii => {
+                    line.push(SHADES[shade]);
+                    line.push(SHADES[shade]);
+                },
+                TextStyle::Ansi(depth) => {
+                    if current != Some(shade) {
+                        line.push_str(&escape_code(palette[shade], depth, true));
+                        current = Some(shade);
+                    }
+                    line.push_str("  ");
+                },
+            }
+        }
+        if let TextStyle::Ansi(_) = style {
+            line.push_str(RESET);
+        }
+        writeln!(writer, "{}", line)?;
+    }
+    writer.flush()
+}
+
+// SGR sequence that sets the foreground or background to the colour
+pub(crate) fn escape_code(color: [u8; 3], depth: ColorDepth, background: bool) -> String {
+    let [red, green, blue] = color;
+    match depth {
+        ColorDepth::TrueColor => format!("\x1b[{};2;{};{};{}m", if background {48} else {38}, red, green, blue),
+        ColorDepth::Ansi256 => format!("\x1b[{};5;{}m", if background {48} else {38}, ansi256(color)),
+        ColorDepth::Ansi16 => {
+            let index = nearest(&ANSI_COLORS, color);
+            let code = if index < 8 {30 + index} else {90 + index - 8};
+            format!("\x1b[{}m", if background {code + 10} else {code})
+        },
+    }
+}
+
+// The nearest colour between the 6x6x6 cube (16 to 231) and the grey ramp (232 to 255)
+fn ansi256(color: [u8; 3]) -> usize {
+    let levels: Vec<[u8; 3]> = CUBE_LEVELS.iter().map(|level| [*level; 3]).collect();
+    let cube = [
+        nearest(&levels, [color[0]; 3]),
+        nearest(&levels, [color[1]; 3]),
+        nearest(&levels, [color[2]; 3]),
+    ];
+    let cube_color = [CUBE_LEVELS[cube[0]], CUBE_LEVELS[cube[1]], CUBE_LEVELS[cube[2]]];
+
+    let greys: Vec<[u8; 3]> = (0..24).map(|grey| [8 + grey * 10; 3]).collect();
+    let grey = nearest(&greys, color);
+
+    if distance(greys[grey], color) < distance(cube_color, color) {
+        232 + grey
+    } else {
+        16 + cube[0] * 36 + cube[1] * 6 + cube[2]
+    }
+}
+
+fn nearest(colors: &[[u8; 3]], color: [u8; 3]) -> usize {
+    (0..colors.len()).min_by_key(|index| distance(colors[*index], color)).unwrap_or(0)
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter().zip(b.iter()).map(|(a, b)| (*a as i32 - *b as i32).pow(2) as u32).sum()
+}
+
+/// Draws the sprite as a sixel image, every pixel becomes a `scale * scale` square
+pub fn write_sixel<W: Write>(sprite: &Sprite, palette: &Palette, scale: NonZeroU32, mut writer: W) -> io::Result<()> {
+    let pixels = scaled_pixels(sprite, scale);
+    let size = pixels.len();
+
+    // Start of the image with square pixels, then the size and the four colours
+    // as colour registers, whose channels go from 0 to 100
+    let mut sixel = format!("\x1bP0;1;0q\"1;1;{};{}", size, size);
+    for (register, color) in palette.iter().enumerate() {
+        let [red, green, blue] = color.map(|channel| channel as u32 * 100 / 255);
+        sixel.push_str(&format!("#{};2;{};{};{}", register, red, green, blue));
+    }
+
+    // Every sixel is a column of 6 pixels, the image is drawn in bands of 6 rows
+    // going over the band once for every colour
+    for band in pixels.chunks(6) {
+        for register in 0..palette.len() as u8 {
+            let sixels: Vec<u8> = (0..size).map(|column| {
+                band.iter().enumerate()
+                    .filter(|(_, row)| row[column] == register)
+                    .fold(0, |sixel, (bit, _)| sixel | (1 << bit))
+            }).collect();
+            if sixels.iter().all(|sixel| *sixel == 0) {
+                continue;
+            }
+            sixel.push_str(&format!("#{}", register));
+            push_sixel_runs(&mut sixel, &sixels);
+            // Back to the start of the band for the next colour
+            sixel.push('$');
+        }
+        sixel.push('-');
+    }
+    sixel.push_str("\x1b\\");
+
+    writer.write_all(sixel.as_bytes())?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+// Repeated sixels are written as "!count" followed by the sixel
+fn push_sixel_runs(output: &mut String, sixels: &[u8]) {
+    let mut index = 0;
+    while index < sixels.len() {
+        let run = sixels[index..].iter().take_while(|sixel| **sixel == sixels[index]).count();
+        let character = (sixels[index] + 63) as char;
+        if run > 3 {
+            output.push_str(&format!("!{}{}", run, character));
+        } else {
+            output.extend(std::iter::repeat_n(character, run));
+        }
+        index += run;
+    }
+}
+
+/// Draws the sprite with the Kitty graphics protocol, every pixel becomes a `scale * scale` square
+pub fn write_kitty<W: Write>(sprite: &Sprite, palette: &Palette, scale: NonZeroU32, mut writer: W) -> io::Result<()> {
+    let pixels = scaled_pixels(sprite, scale);
+    let size = pixels.len();
+    let rgb: Vec<u8> = pixels.iter().flatten().flat_map(|pixel| palette[*pixel as usize]).collect();
+    let data = base64(&rgb);
+
+    // The image has to be sent in chunks of at most 4096 bytes, all but the last one with m=1
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = if index + 1 < chunks.len() {1} else {0};
+        let keys = if index == 0 {
+            format!("a=T,f=24,s={},v={},m={}", size, size, more)
+        } else {
+            format!("m={}", more)
+        };
+        write!(writer, "\x1b_G{};", keys)?;
+        writer.write_all(chunk)?;
+        write!(writer, "\x1b\\")?;
+    }
+    writeln!(writer)?;
+    writer.flush()
+}
+
+// The colour index of every pixel, with every pixel repeated `scale` times in both directions
+fn scaled_pixels(sprite: &Sprite, scale: NonZeroU32) -> Vec<Vec<u8>> {
+    let scale = scale.get() as usize;
+    sprite.pixels.iter()
+        .map(|row| row.iter().flat_map(|pixel| std::iter::repeat_n(pixel & 0b11, scale)).collect::<Vec<u8>>())
+        .flat_map(|row| std::iter::repeat_n(row, scale))
+        .collect()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for position in 0..4 {
+            if position <= chunk.len() {
+                output.push(ALPHABET[(group >> (18 - position * 6)) as usize & 0x3F] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
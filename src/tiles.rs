@@ -0,0 +1,44 @@
+use crate::sprite::{Bitplanes, Sprite, SPRITE_TILES};
+
+// Every tile is 8 rows of 2 bytes, the low bit of the 8 pixels first and then the high bit
+const TILE_LENGTH: usize = 16;
+
+/// The 49 tiles of the centred sprite in the native 2bpp format of the Game Boy, the same bytes
+/// buffers B and C hold after `zip_buffers`. The tiles go column by column, like the game loads them
+pub fn sprite_tiles(sprite: &Sprite) -> Vec<u8> {
+    let mut tiles = Vec::with_capacity(SPRITE_TILES * SPRITE_TILES * TILE_LENGTH);
+    for column in 0..SPRITE_TILES {
+        for y in 0..SPRITE_TILES * 8 {
+            let mut low = 0;
+            let mut high = 0;
+            for x in column * 8..column * 8 + 8 {
+                let pixel = sprite.pixel(x, y);
+                low = (low << 1) | (pixel & 0b01);
+                high = (high << 1) | ((pixel & 0b10) >> 1);
+            }
+            tiles.push(low);
+            tiles.push(high);
+        }
+    }
+    tiles
+}
+
+/// Only the `width * height` tiles of the sprite itself, before it is centred.
+/// The tiles go column by column too, so `tilemap(width, height)` places them
+pub fn bitplane_tiles(planes: &Bitplanes) -> Vec<u8> {
+    // Both planes already hold the rows of every tile one after the other
+    planes.low.iter().zip(planes.high.iter()).flat_map(|(low, high)| [*low, *high]).collect()
+}
+
+/// Tile numbers of a `width * height` tile area row by row, one byte each, for tiles
+/// that go column by column like the ones from `sprite_tiles` and `bitplane_tiles`
+pub fn tilemap(width: u8, height: u8) -> Vec<u8> {
+    let mut map = Vec::with_capacity(width as usize * height as usize);
+    for row in 0..height {
+        for column in 0..width {
+            map.push(column.wrapping_mul(height).wrapping_add(row));
+        }
+    }
+    map
+}
+
@@ -0,0 +1,40 @@
+use pokemon_sprite_decompress::{compress, decompress_with_options, Bitplanes, DecodeError, DecodeOptions, EncodingMode};
+
+const BACK: DecodeOptions = DecodeOptions { glitch: false, back: true, flip: false, no_centring: false };
+
+fn shade(x: usize, y: usize) -> u8 {
+    ((x * 3 + y * 5 + x * y) % 4) as u8
+}
+
+fn encode(width: u8, height: u8) -> Vec<u8> {
+    let pixels: Vec<u8> = (0..height as usize * 8)
+        .flat_map(|y| (0..width as usize * 8).map(move |x| shade(x, y)))
+        .collect();
+    compress(&Bitplanes::from_pixels(width, height, &pixels), EncodingMode::Mode2, 1).unwrap()
+}
+
+#[test]
+fn back_sprites_are_doubled_from_the_top_left_corner() {
+    let sprite = decompress_with_options(&encode(4, 4), &BACK).unwrap();
+    // Every pixel becomes a 2x2 square, the last 4 rows and columns of the 32x32 sprite don't fit
+    for y in 0..56 {
+        for x in 0..56 {
+            assert_eq!(sprite.pixel(x, y), shade(x / 2, y / 2), "pixel ({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn only_4x4_sprites_are_back_sprites() {
+    for (width, height) in [(5, 5), (3, 4), (4, 7), (7, 7)].iter() {
+        match decompress_with_options(&encode(*width, *height), &BACK) {
+            Err(DecodeError::InvalidDimensions { width: found_width, height: found_height }) => {
+                assert_eq!((found_width, found_height), (*width, *height));
+            },
+            result => panic!("expected invalid dimensions for a {}x{} back sprite, got {:?}", width, height, result.map(|sprite| sprite.width)),
+        }
+    }
+    // The game doesn't check, so glitch mode scales them anyway
+    let options = DecodeOptions { glitch: true, ..BACK };
+    assert!(decompress_with_options(&encode(5, 5), &options).is_ok());
+}
@@ -0,0 +1,81 @@
+use pokemon_sprite_decompress::{compress, decompress_with_options, sprite_info, Bitplanes, DecodeOptions, EncodingMode, Sprite};
+
+const SIZES: std::ops::RangeInclusive<u8> = 1..=7;
+
+// Every tile gets a shade from its position, so a sprite that ends up transposed or shifted is caught
+fn shade(x: usize, y: usize) -> u8 {
+    ((x / 8 + 2 * (y / 8)) % 3 + 1) as u8
+}
+
+fn encode(width: u8, height: u8) -> Vec<u8> {
+    let pixels: Vec<u8> = (0..height as usize * 8)
+        .flat_map(|y| (0..width as usize * 8).map(move |x| shade(x, y)))
+        .collect();
+    compress(&Bitplanes::from_pixels(width, height, &pixels), EncodingMode::Mode1, 0).unwrap()
+}
+
+// Checks that the sprite is at the given tile and that everything around it is blank
+fn assert_placed(sprite: &Sprite, left: usize, top: usize) {
+    let (width, height) = (sprite.width as usize * 8, sprite.height as usize * 8);
+    for y in 0..56 {
+        for x in 0..56 {
+            let inside = x >= left * 8 && x < left * 8 + width && y >= top * 8 && y < top * 8 + height;
+            let expected = if inside {shade(x - left * 8, y - top * 8)} else {0};
+            assert_eq!(
+                sprite.pixel(x, y), expected,
+                "{}x{} sprite, pixel ({}, {})", sprite.width, sprite.height, x, y
+            );
+        }
+    }
+}
+
+#[test]
+fn sprites_sit_on_the_bottom_and_are_centred_horizontally() {
+    for width in SIZES {
+        for height in SIZES {
+            let sprite = decompress_with_options(&encode(width, height), &DecodeOptions::default()).unwrap();
+            // Like the game: (7 - width) / 2 rounded up columns on the left, 7 - height rows on top
+            let left = (7 - width as usize).div_ceil(2);
+            let top = 7 - height as usize;
+            assert_placed(&sprite, left, top);
+        }
+    }
+}
+
+#[test]
+fn sprites_stay_in_the_top_left_corner_without_centring() {
+    let options = DecodeOptions { no_centring: true, ..DecodeOptions::default() };
+    for width in SIZES {
+        for height in SIZES {
+            let sprite = decompress_with_options(&encode(width, height), &options).unwrap();
+            assert_placed(&sprite, 0, 0);
+        }
+    }
+}
+
+#[test]
+fn info_reports_the_offsets_used_for_centring() {
+    for width in SIZES {
+        for height in SIZES {
+            let info = sprite_info(&encode(width, height), &DecodeOptions::default()).unwrap();
+            assert_eq!((info.horizontal_offset, info.vertical_offset), ((8 - width) / 2, 7 - height));
+        }
+    }
+}
+
+#[test]
+fn sprites_are_placed_the_same_in_glitch_mode() {
+    let options = DecodeOptions { glitch: true, ..DecodeOptions::default() };
+    for width in SIZES {
+        for height in SIZES {
+            let bytes = encode(width, height);
+            assert_eq!(
+                decompress_with_options(&bytes, &options).unwrap().pixels,
+                decompress_with_options(&bytes, &DecodeOptions::default()).unwrap().pixels,
+                "{}x{} sprite", width, height
+            );
+            let info = sprite_info(&bytes, &options).unwrap();
+            assert_eq!((info.horizontal_offset, info.vertical_offset), ((8 - width) / 2, 7 - height));
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+use pokemon_sprite_decompress::{compress, rom, Bitplanes, EncodingMode};
+
+const BANK_SIZE: usize = 0x4000;
+
+fn sprite_bytes() -> Vec<u8> {
+    let pixels: Vec<u8> = (0..40 * 40).map(|index| (index * 7 / 5 % 4) as u8).collect();
+    compress(&Bitplanes::from_pixels(5, 5, &pixels), EncodingMode::Mode3, 0).unwrap()
+}
+
+// Every test writes its own files, so they can run at the same time
+fn temp_file(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("pokemon_sprite_cli_{}_{}", std::process::id(), name));
+    fs::write(&path, bytes).unwrap();
+    path
+}
+
+// Just enough of Pokémon Yellow for the tool to find Pikachu's sprites: the title, the base stats
+// table with all 151 species and the sprite in bank $B, where the sprites of index $54 are
+fn yellow_rom(sprite: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0; BANK_SIZE * 0xC];
+    rom[0x134..0x134 + 14].copy_from_slice(b"POKEMON YELLOW");
+
+    let base_stats = BANK_SIZE;
+    for position in 0..151 {
+        rom[base_stats + position * 28] = position as u8 + 1;
+    }
+    let sprite_offset = 0xB * BANK_SIZE + 0x100;
+    let pointer = (BANK_SIZE + 0x100) as u16;
+    let pikachu = base_stats + 24 * 28;
+    rom[pikachu + 11..pikachu + 13].copy_from_slice(&pointer.to_le_bytes());
+    rom[pikachu + 13..pikachu + 15].copy_from_slice(&pointer.to_le_bytes());
+    rom[sprite_offset..sprite_offset + sprite.len()].copy_from_slice(sprite);
+    rom
+}
+
+fn run(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pokemon_sprite_decompress"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn decoded(path: &str) -> Vec<u8> {
+    let output = run(&["decode", path, "--format", "ascii"], &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output.stdout
+}
+
+#[test]
+fn a_file_without_a_command_is_decoded() {
+    let path = temp_file("bare.bin", &sprite_bytes());
+    let path = path.to_str().unwrap();
+    let output = run(&[path, "--format", "ascii"], &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, decoded(path));
+}
+
+#[test]
+fn rom_without_a_command_is_extracted() {
+    assert_eq!(rom::index_from_pokedex(25), Some(0x54));
+    let sprite = sprite_bytes();
+    let rom = temp_file("yellow.gb", &yellow_rom(&sprite));
+    let path = temp_file("pikachu.bin", &sprite);
+
+    let output = run(&["--rom", rom.to_str().unwrap(), "--species", "25", "--format", "ascii"], &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, decoded(path.to_str().unwrap()));
+}
+
+#[test]
+fn piped_sprite_without_a_command_is_decoded() {
+    let sprite = sprite_bytes();
+    let path = temp_file("piped.bin", &sprite);
+    let output = run(&["--format", "ascii"], &sprite);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, decoded(path.to_str().unwrap()));
+
+    let output = run(&[], &sprite);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,160 @@
+use pokemon_sprite_decompress::{compress, decompress, decompress_bitplanes, disassemble, encode_optimal, Bitplanes, EncodeError, EncodingMode, Field};
+
+const MODES: [EncodingMode; 3] = [EncodingMode::Mode1, EncodingMode::Mode2, EncodingMode::Mode3];
+
+// Small linear congruential generator, so the sprites are the same on every run
+fn noise(seed: &mut u32) -> u8 {
+    *seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+    (*seed >> 16) as u8
+}
+
+// A sprite with runs of the lightest shade between noisy areas, so both kinds of packets show up
+fn sprite(width: u8, height: u8, seed: u32) -> Bitplanes {
+    let mut seed = seed;
+    let pixels: Vec<u8> = (0..width as usize * height as usize * 64)
+        .map(|_| if noise(&mut seed) < 160 {0} else {noise(&mut seed) & 0b11})
+        .collect();
+    Bitplanes::from_pixels(width, height, &pixels)
+}
+
+// The inverse of the order the decoder writes pairs in: down every column of pairs,
+// four columns of pairs per column of tiles
+fn plane_from_pairs(pairs: &[u8], width: u8, height: u8) -> Vec<u8> {
+    let column_height = height as usize * 8;
+    let mut plane = vec![0; Bitplanes::plane_length(width, height)];
+    for (index, pair) in pairs.iter().enumerate() {
+        let column = index / (column_height * 4);
+        let pair_column = index / column_height % 4;
+        let row = index % column_height;
+        plane[column * column_height + row] |= pair << (6 - pair_column * 2);
+    }
+    plane
+}
+
+fn assert_round_trip(planes: &Bitplanes) {
+    for mode in MODES.iter() {
+        for primary_buffer in 0..2 {
+            let bytes = compress(planes, *mode, primary_buffer).unwrap();
+            assert_eq!(
+                decompress_bitplanes(&bytes).unwrap(), *planes,
+                "{}x{} sprite in {:?} with primary buffer {}", planes.width, planes.height, mode, primary_buffer
+            );
+        }
+    }
+}
+
+#[test]
+fn every_size_round_trips_in_every_mode() {
+    for width in 1..=7 {
+        for height in 1..=7 {
+            assert_round_trip(&sprite(width, height, width as u32 * 8 + height as u32));
+        }
+    }
+}
+
+#[test]
+fn rle_runs_round_trip_around_powers_of_two() {
+    // A run of N zero pairs needs one more bit of length once N + 1 reaches a power of two
+    for length in 1..10 {
+        for zero_pairs in [(1 << length) - 2, (1 << length) - 1, 1 << length].iter().filter(|zero_pairs| **zero_pairs > 0) {
+            let mut pairs = vec![0b01];
+            pairs.extend(std::iter::repeat_n(0, *zero_pairs));
+            pairs.push(0b10);
+            // In mode 2 with an empty high bitplane the low one is compressed as it is
+            let planes = Bitplanes {
+                width: 7,
+                height: 7,
+                low: plane_from_pairs(&pairs, 7, 7),
+                high: vec![0; Bitplanes::plane_length(7, 7)],
+            };
+            assert_round_trip(&planes);
+
+            let bytes = compress(&planes, EncodingMode::Mode2, 0).unwrap();
+            let runs: Vec<usize> = disassemble(&bytes).unwrap().into_iter()
+                .filter_map(|instruction| match instruction.field {
+                    Field::RlePacket { zero_pairs, .. } => Some(zero_pairs),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(runs[0], *zero_pairs);
+        }
+    }
+}
+
+#[test]
+fn data_packet_ending_with_the_plane_has_no_terminator() {
+    let planes = Bitplanes {
+        width: 2,
+        height: 3,
+        low: vec![0xFF; Bitplanes::plane_length(2, 3)],
+        high: vec![0; Bitplanes::plane_length(2, 3)],
+    };
+    assert_round_trip(&planes);
+
+    let bytes = compress(&planes, EncodingMode::Mode2, 0).unwrap();
+    let instructions = disassemble(&bytes).unwrap();
+    let data_packets: Vec<bool> = instructions.iter()
+        .filter_map(|instruction| match &instruction.field {
+            Field::DataPacket { terminated, .. } => Some(*terminated),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(data_packets, vec![false]);
+}
+
+#[test]
+fn empty_planes_round_trip() {
+    for width in 1..=7 {
+        for height in 1..=7 {
+            let plane_length = Bitplanes::plane_length(width, height);
+            assert_round_trip(&Bitplanes { width, height, low: vec![0; plane_length], high: vec![0; plane_length] });
+        }
+    }
+}
+
+#[test]
+fn compressed_length_ignores_trailing_bytes() {
+    for mode in MODES.iter() {
+        let bytes = compress(&sprite(6, 5, 3), *mode, 1).unwrap();
+        let mut padded = bytes.clone();
+        padded.extend_from_slice(&[0xFF, 0x00, 0xA5, 0x5A]);
+        let decoded = decompress(&padded).unwrap();
+        assert_eq!(decoded.compressed_length, bytes.len(), "{:?}", mode);
+        assert_eq!(decoded.pixels, decompress(&bytes).unwrap().pixels, "{:?}", mode);
+    }
+}
+
+#[test]
+fn primary_buffer_is_a_single_bit() {
+    for primary_buffer in [2, 3, 255].iter() {
+        match compress(&sprite(3, 3, 1), EncodingMode::Mode1, *primary_buffer) {
+            Err(EncodeError::InvalidPrimaryBuffer { primary_buffer: found }) => assert_eq!(found, *primary_buffer),
+            result => panic!("expected an invalid primary buffer for {}, got {:?}", primary_buffer, result),
+        }
+    }
+}
+
+#[test]
+fn optimal_encoding_keeps_the_first_shortest_candidate() {
+    let plane_length = Bitplanes::plane_length(5, 5);
+    let empty = Bitplanes { width: 5, height: 5, low: vec![0; plane_length], high: vec![0; plane_length] };
+    let sprites = [sprite(7, 7, 1), sprite(5, 6, 2), sprite(3, 3, 3), empty];
+
+    for planes in sprites.iter() {
+        let encoding = encode_optimal(planes).unwrap();
+
+        let tried: Vec<(EncodingMode, u8)> = encoding.candidates.iter().map(|candidate| (candidate.mode, candidate.primary_buffer)).collect();
+        assert_eq!(tried, vec![
+            (EncodingMode::Mode1, 0), (EncodingMode::Mode1, 1),
+            (EncodingMode::Mode2, 0), (EncodingMode::Mode2, 1),
+            (EncodingMode::Mode3, 0), (EncodingMode::Mode3, 1),
+        ]);
+
+        let smallest = encoding.candidates.iter().map(|candidate| candidate.size).min().unwrap();
+        assert_eq!(encoding.bytes.len(), smallest);
+        let first = encoding.candidates.iter().find(|candidate| candidate.size == smallest).unwrap();
+        assert_eq!((encoding.mode, encoding.primary_buffer), (first.mode, first.primary_buffer));
+
+        assert_eq!(decompress_bitplanes(&encoding.bytes).unwrap(), *planes);
+    }
+}
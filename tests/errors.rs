@@ -0,0 +1,62 @@
+use pokemon_sprite_decompress::{compress, decompress, decompress_with_options, Bitplanes, DecodeError, DecodeOptions, EncodingMode};
+
+fn compressed_sprite() -> Vec<u8> {
+    let pixels: Vec<u8> = (0..40 * 40).map(|index| (index * 7 / 5 % 4) as u8).collect();
+    compress(&Bitplanes::from_pixels(5, 5, &pixels), EncodingMode::Mode3, 0).unwrap()
+}
+
+#[test]
+fn empty_input_is_truncated() {
+    match decompress(&[]) {
+        Err(DecodeError::TruncatedStream { byte_offset: 0, bit_offset: 0 }) => {},
+        result => panic!("expected a truncated stream, got {:?}", result),
+    }
+}
+
+#[test]
+fn cut_short_input_reports_the_first_missing_bit() {
+    let bytes = compressed_sprite();
+    for length in [1, 2, bytes.len() / 2, bytes.len() - 1].iter() {
+        match decompress(&bytes[..*length]) {
+            Err(DecodeError::TruncatedStream { byte_offset, bit_offset: 0 }) => assert_eq!(byte_offset, *length),
+            result => panic!("expected a truncated stream after {} bytes, got {:?}", length, result),
+        }
+    }
+}
+
+#[test]
+fn dimensions_outside_the_box_are_rejected() {
+    // The first byte holds the width and the height in tiles
+    for (header, width, height) in [(0x00, 0, 0), (0x05, 0, 5), (0x50, 5, 0), (0x88, 8, 8), (0x18, 1, 8), (0xF7, 15, 7)].iter() {
+        match decompress(&[*header, 0xFF, 0xFF]) {
+            Err(DecodeError::InvalidDimensions { width: found_width, height: found_height }) => {
+                assert_eq!((found_width, found_height), (*width, *height));
+            },
+            result => panic!("expected invalid dimensions for {:#04X}, got {:?}", header, result),
+        }
+    }
+}
+
+#[test]
+fn dimensions_outside_the_box_decode_in_glitch_mode() {
+    // Every zero pair of these streams is an RLE packet of one pair followed by an empty data packet,
+    // and there are more than enough zeros for the 32x32 tiles that 0x00 means
+    let options = DecodeOptions { glitch: true, ..DecodeOptions::default() };
+    for header in [0x88, 0x00].iter() {
+        let mut bytes = vec![*header];
+        bytes.resize(0x10000, 0);
+        let sprite = decompress_with_options(&bytes, &options).unwrap();
+        assert_eq!((sprite.width, sprite.height), (header >> 4, header & 0x0F));
+    }
+}
+
+#[test]
+fn rle_prefix_longer_than_16_bits_overflows() {
+    // A 1x1 sprite, primary buffer 0 and an RLE packet whose prefix never ends:
+    // the prefix starts at bit 2 of the second byte and its 17th bit is at byte 3 bit 2
+    let bytes = [0x11, 0b00111111, 0xFF, 0xFF, 0xFF];
+    match decompress(&bytes) {
+        Err(DecodeError::BufferOverflow { byte_offset: 3, bit_offset: 2 }) => {},
+        result => panic!("expected a buffer overflow, got {:?}", result),
+    }
+}
@@ -0,0 +1,21 @@
+use std::num::NonZeroU32;
+
+use pokemon_sprite_decompress::{compress, decompress, read_png, write_png, Bitplanes, ColorMapping, EncodingMode, DEFAULT_PALETTE};
+
+#[test]
+fn png_keeps_the_shade_of_every_pixel_as_its_palette_index() {
+    let pixels: Vec<u8> = (0..40 * 48).map(|index| (index * 7 / 5 % 4) as u8).collect();
+    let bytes = compress(&Bitplanes::from_pixels(5, 6, &pixels), EncodingMode::Mode2, 0).unwrap();
+    let sprite = decompress(&bytes).unwrap();
+
+    let mut png = Vec::new();
+    write_png(&sprite, &DEFAULT_PALETTE, NonZeroU32::MIN, &mut png).unwrap();
+    let planes = read_png(&png[..], ColorMapping::PaletteIndex).unwrap();
+
+    assert_eq!((planes.width, planes.height), (7, 7));
+    for y in 0..56 {
+        for x in 0..56 {
+            assert_eq!(planes.pixel(x, y), sprite.pixel(x, y), "pixel ({}, {})", x, y);
+        }
+    }
+}
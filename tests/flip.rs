@@ -0,0 +1,27 @@
+use pokemon_sprite_decompress::{compress, decompress_with_options, Bitplanes, DecodeOptions, EncodingMode};
+
+fn encode(width: u8, height: u8) -> Vec<u8> {
+    let pixels: Vec<u8> = (0..width as usize * height as usize * 64).map(|index| (index * 7 / 5 % 4) as u8).collect();
+    compress(&Bitplanes::from_pixels(width, height, &pixels), EncodingMode::Mode3, 0).unwrap()
+}
+
+// Flipping while decoding must give the same pixels as flipping the decoded sprite
+fn assert_flipped(bytes: &[u8], options: DecodeOptions) {
+    let mut expected = decompress_with_options(bytes, &options).unwrap();
+    expected.flip_horizontally();
+    let flipped = decompress_with_options(bytes, &DecodeOptions { flip: true, ..options }).unwrap();
+    assert_eq!(flipped.pixels, expected.pixels, "{}x{} sprite with {:?}", flipped.width, flipped.height, options);
+}
+
+#[test]
+fn flipped_sprites_are_mirrored_in_the_box() {
+    // Odd widths are centred with the same space on both sides, even widths have one more column on the left
+    for width in 1..=7 {
+        assert_flipped(&encode(width, 5), DecodeOptions::default());
+    }
+}
+
+#[test]
+fn flipped_back_sprites_are_mirrored_after_scaling() {
+    assert_flipped(&encode(4, 4), DecodeOptions { back: true, ..DecodeOptions::default() });
+}
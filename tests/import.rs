@@ -0,0 +1,100 @@
+use pokemon_sprite_decompress::{read_2bpp, read_png, ColorMapping, ImportError};
+
+// An 8 bit PNG, with the palette for indexed images
+fn png(width: u32, height: u32, color: png::ColorType, palette: Option<&[u8]>, data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width, height);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    if let Some(palette) = palette {
+        encoder.set_palette(palette.to_vec());
+    }
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(data).unwrap();
+    writer.finish().unwrap();
+    bytes
+}
+
+// The darkest colour first, so the palette index and the luminance give opposite shades
+const DARK_FIRST: [u8; 12] = [0, 0, 0, 85, 85, 85, 170, 170, 170, 255, 255, 255];
+
+fn indexed(palette: &[u8], pixels: &[u8]) -> Vec<u8> {
+    png(8, 8, png::ColorType::Indexed, Some(palette), pixels)
+}
+
+#[test]
+fn images_must_be_whole_tiles_that_fit_in_the_box() {
+    for (width, height) in [(12, 8), (8, 20), (64, 8), (8, 64)].iter() {
+        let data = vec![255; (width * height) as usize];
+        match read_png(&png(*width, *height, png::ColorType::Grayscale, None, &data)[..], ColorMapping::Auto) {
+            Err(ImportError::InvalidDimensions { width: found_width, height: found_height }) => {
+                assert_eq!((found_width, found_height), (*width, *height));
+            },
+            result => panic!("expected invalid dimensions for {}x{}, got {:?}", width, height, result),
+        }
+    }
+    let planes = read_png(&png(56, 16, png::ColorType::Grayscale, None, &[255; 56 * 16])[..], ColorMapping::Auto).unwrap();
+    assert_eq!((planes.width, planes.height), (7, 2));
+}
+
+#[test]
+fn palette_index_and_luminance_give_their_own_shades() {
+    let pixels: Vec<u8> = (0..64).map(|index| (index % 4) as u8).collect();
+    let image = indexed(&DARK_FIRST, &pixels);
+
+    // By index the colour doesn't matter, by luminance the darkest colour is shade 3
+    for mapping in [ColorMapping::PaletteIndex, ColorMapping::Auto].iter() {
+        let planes = read_png(&image[..], *mapping).unwrap();
+        for (index, pixel) in pixels.iter().enumerate() {
+            assert_eq!(planes.pixel(index % 8, index / 8), *pixel, "{:?}", mapping);
+        }
+    }
+    let planes = read_png(&image[..], ColorMapping::Luminance).unwrap();
+    for (index, pixel) in pixels.iter().enumerate() {
+        assert_eq!(planes.pixel(index % 8, index / 8), 3 - pixel);
+    }
+}
+
+#[test]
+fn palette_index_needs_an_indexed_image_with_four_shades() {
+    let palette: Vec<u8> = DARK_FIRST.iter().chain(&[255, 0, 0]).copied().collect();
+    let mut pixels = vec![0; 64];
+    pixels[9] = 4;
+    match read_png(&indexed(&palette, &pixels)[..], ColorMapping::PaletteIndex) {
+        Err(ImportError::PaletteIndexOutOfRange { index: 4 }) => {},
+        result => panic!("expected palette index 4 to be out of range, got {:?}", result),
+    }
+
+    let gray = png(8, 8, png::ColorType::Grayscale, None, &[0; 64]);
+    match read_png(&gray[..], ColorMapping::PaletteIndex) {
+        Err(ImportError::NotIndexed) => {},
+        result => panic!("expected a grayscale image not to be indexed, got {:?}", result),
+    }
+}
+
+#[test]
+fn square_2bpp_size_comes_from_the_tile_count() {
+    for size in 1..=7u8 {
+        let tiles: Vec<u8> = (0..size as usize * size as usize * 16).map(|index| index as u8).collect();
+        let planes = read_2bpp(&tiles[..], None).unwrap();
+        assert_eq!((planes.width, planes.height), (size, size));
+        // Every row of a tile is the low byte and then the high byte
+        assert_eq!(planes.low[1], tiles[2]);
+        assert_eq!(planes.high[1], tiles[3]);
+    }
+
+    // 10 tiles are not a square, and 64 are bigger than the box
+    for tiles in [10, 64].iter() {
+        match read_2bpp(&vec![0; tiles * 16][..], None) {
+            Err(ImportError::InvalidTileCount { length }) => assert_eq!(length, tiles * 16),
+            result => panic!("expected an invalid tile count for {} tiles, got {:?}", tiles, result),
+        }
+    }
+
+    let planes = read_2bpp(&[0; 5 * 6 * 16][..], Some((5, 6))).unwrap();
+    assert_eq!((planes.width, planes.height), (5, 6));
+    match read_2bpp(&[0; 25 * 16][..], Some((5, 6))) {
+        Err(ImportError::TileCountMismatch { length: 400, width: 5, height: 6 }) => {},
+        result => panic!("expected a tile count mismatch, got {:?}", result),
+    }
+}
@@ -0,0 +1,30 @@
+use pokemon_sprite_decompress::{compress, sprite_info, Bitplanes, DecodeOptions, EncodingMode};
+
+// A blank 1x1 sprite is a single RLE packet of 32 zero pairs per bitplane, 10 bits each:
+// 8 bits of header, 1 of primary buffer, then 1 + 10 bits per bitplane with the mode in between
+fn blank(mode: EncodingMode, primary_buffer: u8) -> Vec<u8> {
+    compress(&Bitplanes { width: 1, height: 1, low: vec![0; 8], high: vec![0; 8] }, mode, primary_buffer).unwrap()
+}
+
+#[test]
+fn json_has_every_field() {
+    // Mode 3 takes one more bit than mode 1, which needs a fifth byte
+    for (mode, number, primary_buffer, compressed_length) in [(EncodingMode::Mode1, 1, 0, 4), (EncodingMode::Mode3, 3, 1, 5)].iter() {
+        let info = sprite_info(&blank(*mode, *primary_buffer), &DecodeOptions::default()).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&info.to_json()).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "width": 1,
+            "height": 1,
+            "vertical_offset": 6,
+            "horizontal_offset": 3,
+            "primary_buffer": primary_buffer,
+            "initial_packets": [0, 0],
+            "encoding_mode": number,
+            "compressed_length": compressed_length,
+            "rle_packets": 2,
+            "data_packets": 0,
+            "longest_run": 32,
+            "longest_data_packet": 0,
+        }));
+    }
+}
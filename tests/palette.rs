@@ -0,0 +1,55 @@
+use pokemon_sprite_decompress::{load_palette, parse_palette, PaletteError, DMG_PALETTE};
+
+const EXPECTED: [[u8; 3]; 4] = [[0xE0, 0xF8, 0xD0], [0x88, 0xC0, 0x70], [0x34, 0x68, 0x56], [0x08, 0x18, 0x20]];
+
+#[test]
+fn text_palettes_have_one_colour_per_line() {
+    let text = "; Lightest to darkest\n#E0F8D0\n#88c070 ; light\n\n  #346856\n081820\n";
+    assert_eq!(parse_palette(text).unwrap(), EXPECTED);
+}
+
+#[test]
+fn json_palettes_take_hex_strings_and_channel_lists() {
+    let list = r##"["#E0F8D0", "#88C070", [52, 104, 86], "#081820"]"##;
+    assert_eq!(parse_palette(list).unwrap(), EXPECTED);
+    let object = r##"  {"colors": [[224, 248, 208], "#88C070", "#346856", [8, 24, 32]]}"##;
+    assert_eq!(parse_palette(object).unwrap(), EXPECTED);
+}
+
+#[test]
+fn invalid_colours_are_rejected() {
+    for (text, color) in [
+        ("#E0F8D0\n#88C07\n#346856\n#081820", "#88C07"),
+        ("#E0F8D0\n#88C0G0\n#346856\n#081820", "#88C0G0"),
+        (r##"["#E0F8D0", "#88C070", [52, 104], "#081820"]"##, "[52,104]"),
+        (r##"["#E0F8D0", "#88C070", [52, 104, 256], "#081820"]"##, "[52,104,256]"),
+    ].iter() {
+        match parse_palette(text) {
+            Err(PaletteError::InvalidColor(found)) => assert_eq!(found, *color),
+            result => panic!("expected {} to be an invalid colour, got {:?}", color, result),
+        }
+    }
+}
+
+#[test]
+fn palettes_need_four_colours() {
+    for (text, found) in [("#E0F8D0\n#88C070\n#346856", 3), (r##"["#E0F8D0", "#88C070", "#346856", "#081820", "#000000"]"##, 5), ("", 0)].iter() {
+        match parse_palette(text) {
+            Err(PaletteError::WrongColorCount { found: count }) => assert_eq!(count, *found),
+            result => panic!("expected {} colours to be the wrong count, got {:?}", found, result),
+        }
+    }
+    match parse_palette(r##"{"colors": "#E0F8D0"}"##) {
+        Err(PaletteError::InvalidJson(_)) => {},
+        result => panic!("expected invalid JSON, got {:?}", result),
+    }
+}
+
+#[test]
+fn built_in_palettes_are_found_by_name() {
+    assert_eq!(load_palette("dmg").unwrap(), DMG_PALETTE);
+    match load_palette("no-such-palette") {
+        Err(PaletteError::UnknownName(name)) => assert_eq!(name, "no-such-palette"),
+        result => panic!("expected an unknown palette, got {:?}", result),
+    }
+}
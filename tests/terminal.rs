@@ -0,0 +1,116 @@
+use std::num::NonZeroU32;
+
+use pokemon_sprite_decompress::{
+    write_kitty, write_sixel, write_text, ColorDepth, EncodingMode, Palette, Sprite, TextStyle, DEFAULT_PALETTE, SPRITE_PIXELS,
+};
+
+// White, red, a grey in the middle of the grey ramp and black
+const PALETTE: Palette = [[255, 255, 255], [255, 0, 0], [128, 128, 128], [0, 0, 0]];
+
+// A diamond of every shade in the top left tile, everything else the lightest shade
+const DIAMOND: [&str; 8] = [
+    "00033000",
+    "00322300",
+    "03211230",
+    "32100123",
+    "32100123",
+    "03211230",
+    "00322300",
+    "00033000",
+];
+
+fn diamond() -> Sprite {
+    let mut pixels = [[0; SPRITE_PIXELS]; SPRITE_PIXELS];
+    for (y, row) in DIAMOND.iter().enumerate() {
+        for (x, shade) in row.bytes().enumerate() {
+            pixels[y][x] = shade - b'0';
+        }
+    }
+    Sprite { width: 1, height: 1, pixels, compressed_length: 0, encoding_mode: EncodingMode::Mode1, primary_buffer: 0 }
+}
+
+#[test]
+fn ascii_text_matches_the_snapshot() {
+    let mut text = Vec::new();
+    write_text(&diamond(), &DEFAULT_PALETTE, TextStyle::Ascii, &mut text).unwrap();
+
+    let mut expected = String::new();
+    for row in [
+        "      ####",
+        "    ##++++##",
+        "  ##++....++##",
+        "##++..    ..++##",
+        "##++..    ..++##",
+        "  ##++....++##",
+        "    ##++++##",
+        "      ####",
+    ].iter() {
+        expected.push_str(&format!("{:<112}\n", row));
+    }
+    for _ in 8..SPRITE_PIXELS {
+        expected.push_str(&format!("{:112}\n", ""));
+    }
+    assert_eq!(String::from_utf8(text).unwrap(), expected);
+}
+
+// The first row of the sprite has one pixel of every shade, so the line starts with the four colours
+fn first_line(depth: ColorDepth) -> String {
+    let mut sprite = diamond();
+    sprite.pixels[0] = [0; SPRITE_PIXELS];
+    sprite.pixels[0][..4].copy_from_slice(&[0, 1, 2, 3]);
+    let mut text = Vec::new();
+    write_text(&sprite, &PALETTE, TextStyle::Ansi(depth), &mut text).unwrap();
+    String::from_utf8(text).unwrap().lines().next().unwrap().to_string()
+}
+
+#[test]
+fn colours_are_rounded_to_the_256_colours() {
+    // White and red are in the colour cube, the grey is closer to the grey ramp than to the cube
+    let line = first_line(ColorDepth::Ansi256);
+    assert!(line.starts_with("\x1b[48;5;231m  \x1b[48;5;196m  \x1b[48;5;244m  \x1b[48;5;16m  \x1b[48;5;231m  "), "{:?}", line);
+}
+
+#[test]
+fn colours_are_rounded_to_the_16_colours() {
+    // Bright white, bright red, bright black and black
+    let line = first_line(ColorDepth::Ansi16);
+    assert!(line.starts_with("\x1b[107m  \x1b[101m  \x1b[100m  \x1b[40m  \x1b[107m  "), "{:?}", line);
+}
+
+#[test]
+fn true_colour_keeps_the_palette() {
+    let line = first_line(ColorDepth::TrueColor);
+    assert!(line.starts_with("\x1b[48;2;255;255;255m  \x1b[48;2;255;0;0m  \x1b[48;2;128;128;128m  \x1b[48;2;0;0;0m  "), "{:?}", line);
+}
+
+#[test]
+fn sixel_images_have_the_size_and_the_colour_registers() {
+    let mut output = Vec::new();
+    write_sixel(&diamond(), &PALETTE, NonZeroU32::MIN, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    // Square pixels, 56x56 and the channels from 0 to 100
+    assert!(output.starts_with("\x1bP0;1;0q\"1;1;56;56#0;2;100;100;100#1;2;100;0;0#2;2;50;50;50#3;2;0;0;0#"), "{:?}", output);
+    assert!(output.ends_with("-\x1b\\\n"), "{:?}", output);
+    // One line of sixels for every 6 rows
+    assert_eq!(output.matches('-').count(), SPRITE_PIXELS.div_ceil(6));
+}
+
+#[test]
+fn kitty_images_are_sent_in_chunks() {
+    let mut output = Vec::new();
+    write_kitty(&diamond(), &PALETTE, NonZeroU32::MIN, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    // 56x56 RGB pixels are 12544 characters of base64, that's 3 full chunks and a last one
+    let chunks: Vec<&str> = output.trim_end_matches('\n').split_terminator("\x1b\\").collect();
+    assert_eq!(chunks.len(), 4);
+    // The diamond starts with three white pixels and two black ones
+    assert!(chunks[0].starts_with("\x1b_Ga=T,f=24,s=56,v=56,m=1;////////////AAAAAAAA////"), "{:?}", &chunks[0][..60]);
+    assert!(chunks[1].starts_with("\x1b_Gm=1;"));
+    assert!(chunks[2].starts_with("\x1b_Gm=1;"));
+    assert!(chunks[3].starts_with("\x1b_Gm=0;"));
+    // Every pixel is 3 bytes, which are 4 characters of base64
+    let data: usize = chunks.iter().map(|chunk| chunk.len() - chunk.find(';').unwrap() - 1).sum();
+    assert_eq!(data, 56 * 56 * 4);
+    assert!(output.ends_with("\x1b\\\n"));
+}
@@ -0,0 +1,57 @@
+use pokemon_sprite_decompress::{bitplane_tiles, compress, decompress, sprite_tiles, tilemap, Bitplanes, EncodingMode};
+
+fn shade(x: usize, y: usize) -> u8 {
+    ((x * 3 + y * 5 + x * y) % 4) as u8
+}
+
+// The 2bpp colour of a pixel of a tile: row by row, the low byte and then the high byte
+fn tile_pixel(tile: &[u8], x: usize, y: usize) -> u8 {
+    let low = (tile[y * 2] >> (7 - x)) & 1;
+    let high = (tile[y * 2 + 1] >> (7 - x)) & 1;
+    (high << 1) | low
+}
+
+#[test]
+fn tilemap_places_the_tiles_of_a_5x5_sprite() {
+    let pixels: Vec<u8> = (0..40).flat_map(|y| (0..40).map(move |x| shade(x, y))).collect();
+    let planes = Bitplanes::from_pixels(5, 5, &pixels);
+    let tiles = bitplane_tiles(&planes);
+    let map = tilemap(5, 5);
+    assert_eq!(tiles.len(), 25 * 16);
+    assert_eq!(map.len(), 25);
+
+    // The tiles go down every column first, and the map is row by row
+    assert_eq!(&map[..7], &[0, 5, 10, 15, 20, 1, 6]);
+    for row in 0..5 {
+        for column in 0..5 {
+            let tile = map[row * 5 + column] as usize;
+            for y in 0..8 {
+                for x in 0..8 {
+                    assert_eq!(
+                        tile_pixel(&tiles[tile * 16..tile * 16 + 16], x, y), shade(column * 8 + x, row * 8 + y),
+                        "tile {} at row {} column {}", tile, row, column
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn sprite_tiles_cover_the_whole_box() {
+    let pixels: Vec<u8> = (0..40).flat_map(|y| (0..40).map(move |x| shade(x, y))).collect();
+    let sprite = decompress(&compress(&Bitplanes::from_pixels(5, 5, &pixels), EncodingMode::Mode1, 0).unwrap()).unwrap();
+    let tiles = sprite_tiles(&sprite);
+    assert_eq!(tiles.len(), 49 * 16);
+
+    let map = tilemap(7, 7);
+    for (position, tile) in map.iter().enumerate() {
+        let (row, column) = (position / 7, position % 7);
+        let tile = *tile as usize;
+        for y in 0..8 {
+            for x in 0..8 {
+                assert_eq!(tile_pixel(&tiles[tile * 16..tile * 16 + 16], x, y), sprite.pixel(column * 8 + x, row * 8 + y));
+            }
+        }
+    }
+}